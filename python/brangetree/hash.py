@@ -1,6 +1,11 @@
 import hashlib
 import math
 
+try:
+    from . import _native
+except ImportError:
+    _native = None
+
 
 class Marker:
     def __init__(self, name, hash):
@@ -18,7 +23,23 @@ B = Marker("B", (0).to_bytes(4, "big"))
 E = Marker("E", (pow(2, 32) - 1).to_bytes(4, "big"))
 
 
+def _marker(v):
+    if v == 0:
+        return B
+    if v == pow(2, 32) - 1:
+        return E
+    return v
+
+
 def gen_leaves(bits, fill=False):
+    if _native:
+        for a, b in _native.gen_leaves(bits, fill):
+            yield (_marker(a), _marker(b))
+        return
+    yield from _gen_leaves(bits, fill)
+
+
+def _gen_leaves(bits, fill=False):
     left = B
     rev_start = None
     leaf_idx = 0
@@ -69,7 +90,18 @@ def terminator_hash(cache, depth=0, leaf_hash=leaf_hash, branch_hash=branch_hash
     return h
 
 
+_DEFAULT_HASHES = (leaf_hash, branch_hash)
+
+
+def _leaf_index(v):
+    return int.from_bytes(v.hash, "big") if isinstance(v, Marker) else v
+
+
 def hash_leaves(leaves, leaf_hash=leaf_hash, branch_hash=branch_hash, fill=True):
+    if _native and (leaf_hash, branch_hash) == _DEFAULT_HASHES:
+        leaves = ((_leaf_index(a), _leaf_index(b)) for (a, b) in leaves)
+        return _native.hash_leaves(leaves, fill)
+
     stack = []
     depth = -1
     term_cache = []
@@ -115,3 +147,23 @@ def hash_leaves(leaves, leaf_hash=leaf_hash, branch_hash=branch_hash, fill=True)
         "leaf_count_filled": leaf_count_filled,
         "root": root,
     }
+
+
+def hash_zipped(filename, fill=True):
+    if not _native:
+        from .util import iter_zipped_bits
+
+        return hash_leaves(gen_leaves(iter_zipped_bits(filename)), fill=fill)
+    return _native.hash_zipped(filename, fill)
+
+
+def find_merkle_path(filename, index, fill=True):
+    if not _native:
+        raise NotImplementedError("find_merkle_path requires the native extension")
+    return _native.find_merkle_path(filename, index, fill)
+
+
+def verify_merkle_path(leaf_range, path, root):
+    if not _native:
+        raise NotImplementedError("verify_merkle_path requires the native extension")
+    return _native.verify_merkle_path(leaf_range, path, root)
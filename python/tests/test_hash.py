@@ -0,0 +1,51 @@
+import itertools
+import os
+import random
+import sys
+import unittest
+
+sys.path.append(os.path.join(os.path.dirname(__file__), os.pardir))
+
+from brangetree import hash as brt_hash
+from brangetree.hash import B, E
+
+
+class GenLeavesTest(unittest.TestCase):
+    def bit_cases(self):
+        rand = random.Random(1)
+        yield []
+        yield [0] * 10
+        yield [1] * 10
+        yield [1, 0, 0, 1, 1, 0, 1]
+        yield [0] * 64 + [1] * 64 + [0] * 3
+        for _ in range(20):
+            yield [int(rand.random() < 0.3) for _ in range(rand.randrange(1, 300))]
+
+    @unittest.skipUnless(brt_hash._native, "native extension not available")
+    def test_native_matches_python(self):
+        for bits in self.bit_cases():
+            for fill in (False, True):
+                native = list(brt_hash.gen_leaves(bits, fill))
+                python = list(brt_hash._gen_leaves(bits, fill))
+                self.assertEqual(len(native), len(python))
+                for n, p in zip(native, python):
+                    # the bounds are the same marker objects
+                    self.assertEqual(type(n[0]), type(p[0]))
+                    self.assertEqual(type(n[1]), type(p[1]))
+                    self.assertEqual(n, p)
+
+    def test_lazy(self):
+        # an endless bit source must still yield leaves
+        bits = itertools.cycle([0, 1, 0])
+        leaves = list(itertools.islice(brt_hash.gen_leaves(bits), 3))
+        self.assertEqual(leaves, [(B, 2), (2, 5), (5, 8)])
+
+    def test_markers(self):
+        leaves = list(brt_hash._gen_leaves([0, 1, 0], fill=True))
+        self.assertIs(leaves[0][0], B)
+        self.assertIs(leaves[-1][1], E)
+        self.assertEqual(leaves[0][1], 2)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,23 @@
+fn main() {
+    #[cfg(feature = "cbindgen")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-env-changed=BRT_UPDATE_HEADER");
+        // only the C API is parsed, so that public constants elsewhere in the
+        // crate (such as status_list::MIN_LIST_BYTES) stay out of the header
+        let config = cbindgen::Config::from_root_or_default(&crate_dir);
+        let bindings = cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(std::path::Path::new(&crate_dir).join("src/ffi.rs"))
+            .generate()
+            .expect("Unable to generate bindings");
+        bindings.write_to_file(std::path::Path::new(&out_dir).join("brangetree.h"));
+        // the checked-in header is only replaced on request
+        if std::env::var_os("BRT_UPDATE_HEADER").is_some() {
+            bindings.write_to_file(std::path::Path::new(&crate_dir).join("include/brangetree.h"));
+        }
+    }
+}
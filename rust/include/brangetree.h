@@ -0,0 +1,135 @@
+#ifndef BRANGETREE_H
+#define BRANGETREE_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define BRT_HASH_SIZE 32
+
+typedef enum BrtErrorCode {
+  BRT_ERROR_CODE_SUCCESS = 0,
+  BRT_ERROR_CODE_IO = 1,
+  BRT_ERROR_CODE_UNEXPECTED = 2,
+  BRT_ERROR_CODE_INVALID_ARGUMENT = 3,
+  BRT_ERROR_CODE_BUFFER_TOO_SMALL = 4,
+  BRT_ERROR_CODE_NOT_FOUND = 5,
+  BRT_ERROR_CODE_INVALID_PROOF = 6,
+  BRT_ERROR_CODE_CANCELLED = 7,
+} BrtErrorCode;
+
+/**
+ * Opaque handle to a non-revocation proof
+ */
+typedef struct BrtProof BrtProof;
+
+/**
+ * Hash a bitmap buffer, writing the 32 byte root to `root_out`.
+ *
+ * # Safety
+ *
+ * `bitmap` must point to `bitmap_len` readable bytes and `root_out` to
+ * `BRT_HASH_SIZE` writable bytes. The count outputs may be null.
+ */
+enum BrtErrorCode brt_hash_bitmap(const uint8_t *bitmap,
+                                  size_t bitmap_len,
+                                  bool fill,
+                                  uint8_t *root_out,
+                                  size_t *leaf_count_out,
+                                  size_t *filled_count_out);
+
+/**
+ * Produce a proof that `index` is not revoked in a bitmap buffer.
+ *
+ * Returns `NotFound` when the index is revoked or outside the registry,
+ * which holds the indices `1..=bitmap_len * 8`.
+ *
+ * # Safety
+ *
+ * `bitmap` must point to `bitmap_len` readable bytes and `proof_out` must be
+ * a valid pointer. The returned handle must be released with `brt_proof_free`.
+ */
+enum BrtErrorCode brt_proof_from_bitmap(const uint8_t *bitmap,
+                                        size_t bitmap_len,
+                                        uint32_t index,
+                                        struct BrtProof **proof_out);
+
+/**
+ * Load a proof previously written by `brt_proof_encode`.
+ *
+ * # Safety
+ *
+ * `data` must point to `data_len` readable bytes and `proof_out` must be
+ * a valid pointer. The returned handle must be released with `brt_proof_free`.
+ */
+enum BrtErrorCode brt_proof_decode(const uint8_t *data,
+                                   size_t data_len,
+                                   struct BrtProof **proof_out);
+
+/**
+ * Write the encoded proof to `buf`, storing the required size in `written_out`.
+ *
+ * Returns `BufferTooSmall` if `buf_len` is insufficient, in which case
+ * `written_out` still receives the required size.
+ *
+ * # Safety
+ *
+ * `proof` must be a live handle, `buf` must point to `buf_len` writable bytes
+ * and `written_out` must be a valid pointer.
+ */
+enum BrtErrorCode brt_proof_encode(const struct BrtProof *proof,
+                                   uint8_t *buf,
+                                   size_t buf_len,
+                                   size_t *written_out);
+
+/**
+ * Read the `(left, right)` range covered by a proof.
+ *
+ * # Safety
+ *
+ * `proof` must be a live handle and the outputs valid pointers.
+ */
+enum BrtErrorCode brt_proof_range(const struct BrtProof *proof,
+                                  uint32_t *left_out,
+                                  uint32_t *right_out);
+
+/**
+ * Copy the 32 byte root of the tree a proof was produced from.
+ *
+ * Returns `NotFound` for decoded proofs, which do not carry a root.
+ *
+ * # Safety
+ *
+ * `proof` must be a live handle and `root_out` must point to
+ * `BRT_HASH_SIZE` writable bytes.
+ */
+enum BrtErrorCode brt_proof_root(const struct BrtProof *proof, uint8_t *root_out);
+
+/**
+ * Check that a proof shows `index` is not revoked in the tree with the given root.
+ *
+ * Returns `Success` for a valid proof and `InvalidProof` otherwise.
+ *
+ * # Safety
+ *
+ * `proof` must be a live handle and `root` must point to `BRT_HASH_SIZE`
+ * readable bytes.
+ */
+enum BrtErrorCode brt_proof_verify(const struct BrtProof *proof,
+                                   uint32_t index,
+                                   const uint8_t *root);
+
+/**
+ * Release a proof handle.
+ *
+ * # Safety
+ *
+ * `proof` must be null or a handle not previously freed.
+ */
+void brt_proof_free(struct BrtProof *proof);
+
+#endif /* BRANGETREE_H */
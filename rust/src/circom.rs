@@ -0,0 +1,114 @@
+use paired::bls12_381::Fr;
+
+use crate::path::{Path, PathJoin};
+use crate::poseidon::fr_to_decimal;
+
+/// Circuit inputs for a non-revocation path through a Poseidon range tree.
+///
+/// Direction bits follow the usual circom Merkle convention: `0` when the
+/// current node is the left child (the sibling is on the right), `1` otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CircomWitness {
+    pub left: u32,
+    pub right: u32,
+    pub siblings: Vec<Fr>,
+    pub path_indices: Vec<u8>,
+    pub root: Fr,
+}
+
+impl CircomWitness {
+    pub fn new(range: (u32, u32), path: &Path<Fr>, root: Fr) -> Self {
+        let (siblings, path_indices) = path
+            .join
+            .iter()
+            .map(|part| match part {
+                PathJoin::Left(h) => (*h, 1),
+                PathJoin::Right(h) => (*h, 0),
+            })
+            .unzip();
+        Self {
+            left: range.0,
+            right: range.1,
+            siblings,
+            path_indices,
+            root,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Render the witness as a snarkjs `input.json` object, with field
+    /// elements as decimal strings.
+    pub fn to_json(&self) -> String {
+        let siblings: Vec<String> = self
+            .siblings
+            .iter()
+            .map(|h| format!("\"{}\"", fr_to_decimal(h)))
+            .collect();
+        let indices: Vec<String> = self
+            .path_indices
+            .iter()
+            .map(|d| format!("\"{}\"", d))
+            .collect();
+        format!(
+            "{{\"left\":\"{}\",\"right\":\"{}\",\"siblings\":[{}],\"pathIndices\":[{}],\"root\":\"{}\"}}",
+            self.left,
+            self.right,
+            siblings.join(","),
+            indices.join(","),
+            fr_to_decimal(&self.root)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::process_bitmap;
+    use crate::poseidon::{fr_from_decimal, range_leaf_scalar, PoseidonFold};
+    use crate::range::{make_range, RangeParser, RangePathTracker};
+    use generic_array::typenum::U2;
+    use neptune::poseidon::{Poseidon, PoseidonConstants};
+    use paired::bls12_381::Bls12;
+
+    #[test]
+    fn test_witness() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let mut bitmap = [0u8; 8];
+        bitmap[0] = 0x90;
+        bitmap[5] = 0x01;
+        let tracker = RangePathTracker::new(PoseidonFold::new(&constants), 20);
+        let mut tracker = process_bitmap(&bitmap, RangeParser::new(tracker)).unwrap();
+        tracker.fill();
+        let (range, path, root) = tracker.result();
+        let witness = CircomWitness::new(range.unwrap(), &path.unwrap(), root.unwrap());
+        assert_eq!((witness.left, witness.right), (4, 48));
+        assert_eq!(witness.depth(), 2);
+        assert_eq!(witness.path_indices, vec![0, 1]);
+
+        // recompute the root as a circuit would, from the witness values only
+        let mut hasher = Poseidon::new(&constants);
+        let mut node = range_leaf_scalar(&make_range(witness.left, witness.right));
+        for (sibling, dir) in witness.siblings.iter().zip(&witness.path_indices) {
+            let (l, r) = if *dir == 0 {
+                (node, *sibling)
+            } else {
+                (*sibling, node)
+            };
+            hasher.reset();
+            hasher.input(l).unwrap();
+            hasher.input(r).unwrap();
+            node = hasher.hash();
+        }
+        assert_eq!(node, witness.root);
+
+        let json = witness.to_json();
+        assert!(json.starts_with("{\"left\":\"4\",\"right\":\"48\",\"siblings\":[\""));
+        assert!(json.contains("\"pathIndices\":[\"0\",\"1\"]"));
+        let root_str = json.rsplit("\"root\":\"").next().unwrap();
+        let root_str = root_str.trim_end_matches("\"}");
+        assert_eq!(fr_from_decimal(root_str), Some(witness.root));
+    }
+}
@@ -0,0 +1,291 @@
+//! Combinators over `TreeFold`, which may be nested within each other and
+//! within trackers such as `PathTracker`.
+
+use core::marker::PhantomData;
+
+use crate::tree::TreeFold;
+
+/// Run two folds over the same leaves in a single pass, producing a pair of
+/// nodes for each node of the tree.
+#[derive(Clone, Debug, Default)]
+pub struct TeeFold<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> TeeFold<A, B>
+where
+    A: TreeFold,
+    B: TreeFold<Leaf = A::Leaf, Error = A::Error>,
+{
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A, B> TreeFold for TeeFold<A, B>
+where
+    A: TreeFold,
+    B: TreeFold<Leaf = A::Leaf, Error = A::Error>,
+{
+    type Leaf = A::Leaf;
+    type Target = (A::Target, B::Target);
+    type Error = A::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        Ok((self.first.input(leaf)?, self.second.input(leaf)?))
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        Ok((self.first.fold(&a.0, &b.0)?, self.second.fold(&a.1, &b.1)?))
+    }
+
+    fn start_fill(&mut self) {
+        self.first.start_fill();
+        self.second.start_fill();
+    }
+
+    fn end_fill(&mut self) {
+        self.first.end_fill();
+        self.second.end_fill();
+    }
+}
+
+/// Convert each leaf before passing it to the base fold. The fill leaf given
+/// to the folder is converted in the same way.
+pub struct MapFold<T, F, L> {
+    base: T,
+    f: F,
+    _pd: PhantomData<fn(&L)>,
+}
+
+impl<T, F, L> MapFold<T, F, L>
+where
+    T: TreeFold,
+    F: FnMut(&L) -> T::Leaf,
+{
+    pub fn new(base: T, f: F) -> Self {
+        Self {
+            base,
+            f,
+            _pd: PhantomData,
+        }
+    }
+
+    pub fn base(&self) -> &T {
+        &self.base
+    }
+
+    pub fn into_inner(self) -> T {
+        self.base
+    }
+}
+
+impl<T, F, L> TreeFold for MapFold<T, F, L>
+where
+    T: TreeFold,
+    F: FnMut(&L) -> T::Leaf,
+{
+    type Leaf = L;
+    type Target = T::Target;
+    type Error = T::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let leaf = (self.f)(leaf);
+        self.base.input(&leaf)
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        self.base.fold(a, b)
+    }
+
+    fn start_fill(&mut self) {
+        self.base.start_fill()
+    }
+
+    fn end_fill(&mut self) {
+        self.base.end_fill()
+    }
+}
+
+/// The calls made to a `CountingFold`. Inputs and folds made while filling
+/// are counted separately.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FoldCounts {
+    pub inputs: usize,
+    pub folds: usize,
+    pub fill_inputs: usize,
+    pub fill_folds: usize,
+    pub fills: usize,
+}
+
+/// Count the calls made to the base fold.
+#[derive(Clone, Debug, Default)]
+pub struct CountingFold<T> {
+    base: T,
+    counts: FoldCounts,
+    fill: bool,
+}
+
+impl<T: TreeFold> CountingFold<T> {
+    pub fn new(base: T) -> Self {
+        Self {
+            base,
+            counts: FoldCounts::default(),
+            fill: false,
+        }
+    }
+
+    pub fn counts(&self) -> FoldCounts {
+        self.counts
+    }
+
+    pub fn base(&self) -> &T {
+        &self.base
+    }
+
+    pub fn into_inner(self) -> T {
+        self.base
+    }
+}
+
+impl<T: TreeFold> TreeFold for CountingFold<T> {
+    type Leaf = T::Leaf;
+    type Target = T::Target;
+    type Error = T::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        if self.fill {
+            self.counts.fill_inputs += 1;
+        } else {
+            self.counts.inputs += 1;
+        }
+        self.base.input(leaf)
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        if self.fill {
+            self.counts.fill_folds += 1;
+        } else {
+            self.counts.folds += 1;
+        }
+        self.base.fold(a, b)
+    }
+
+    fn start_fill(&mut self) {
+        self.fill = true;
+        self.counts.fills += 1;
+        self.base.start_fill()
+    }
+
+    fn end_fill(&mut self) {
+        self.fill = false;
+        self.base.end_fill()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hash::{HashFold, Rfc6962Fold};
+    use crate::input::process_bitmap;
+    use crate::path::PathTracker;
+    use crate::range::{ct_range_hasher, range_hasher, RangeParser, RangeTreeFolder};
+    use crate::tree::test::TestFold;
+    use crate::tree::TreeFolder;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_tee() {
+        let bitmap = [0x55, 0x0f, 0x81];
+        let parser = RangeParser::new(RangeTreeFolder::new(TeeFold::new(
+            HashFold::<Sha256, [u8; 8]>::new(),
+            Rfc6962Fold::<Sha256, [u8; 8]>::new(),
+        )));
+        let mut tee = process_bitmap(&bitmap, parser).unwrap();
+        tee.fill();
+        let (first, second) = tee.result().unwrap();
+
+        let mut expect =
+            process_bitmap(&bitmap, RangeParser::new(range_hasher::<Sha256>())).unwrap();
+        expect.fill();
+        assert_eq!(first, expect.result().unwrap());
+        let mut expect =
+            process_bitmap(&bitmap, RangeParser::new(ct_range_hasher::<Sha256>())).unwrap();
+        expect.fill();
+        assert_eq!(second, expect.result().unwrap());
+    }
+
+    #[test]
+    fn test_tee_path() {
+        let leaves: Vec<String> = (0..11).map(|n| n.to_string()).collect();
+        let fill = Some("E".to_string());
+        let tracker = PathTracker::new(
+            TeeFold::new(
+                TestFold {},
+                MapFold::new(TestFold {}, |leaf: &String| leaf.repeat(2)),
+            ),
+            Some(6),
+        );
+        let (result, tracker) = TreeFolder::fold(tracker, leaves.clone(), fill.clone()).unwrap();
+        let (first, second) = result.unwrap();
+        let doubled: Vec<String> = leaves.iter().map(|leaf| leaf.repeat(2)).collect();
+        let (expect, _) = TreeFolder::fold(TestFold {}, doubled, Some("EE".to_string())).unwrap();
+        assert_eq!(Some(second.clone()), expect);
+
+        let (expect, expect_path) =
+            TreeFolder::fold(PathTracker::new(TestFold {}, Some(6)), leaves, fill).unwrap();
+        assert_eq!(Some(first.clone()), expect);
+        let expect_path = expect_path.path_result().unwrap();
+
+        let path = tracker.path_result().unwrap();
+        assert_eq!(path.leaf, ("6".to_string(), "66".to_string()));
+        assert_eq!(path.join.len(), expect_path.join.len());
+        let root = path.fold(|l, r| (format!("[{},{}]", l.0, r.0), format!("[{},{}]", l.1, r.1)));
+        assert_eq!(root, (first, second));
+    }
+
+    #[test]
+    fn test_counting() {
+        let leaves: Vec<String> = (0..11).map(|n| n.to_string()).collect();
+        let tracker = PathTracker::new(CountingFold::new(TestFold {}), Some(3));
+        let (_, tracker) = TreeFolder::fold(tracker, leaves.clone(), None).unwrap();
+        assert!(tracker.path_result().is_some());
+        let mut folder = TreeFolder::new(CountingFold::new(TestFold {}));
+        folder.extend(leaves).unwrap();
+        assert_eq!(
+            folder.base().counts(),
+            FoldCounts {
+                inputs: 11,
+                folds: 8,
+                ..Default::default()
+            }
+        );
+        folder.fill("E".to_string()).unwrap();
+        let (_, counting) = folder.result().unwrap();
+        assert_eq!(
+            counting.counts(),
+            FoldCounts {
+                inputs: 11,
+                folds: 12,
+                fill_inputs: 1,
+                fill_folds: 2,
+                fills: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Consistency proofs between two sizes of an append-only tree.
+//!
+//! Without filling, `TreeFolder` produces the tree shape of the RFC 6962
+//! Merkle tree hash, so with `Rfc6962Fold` the roots, paths and consistency
+//! proofs produced here are compatible with Certificate Transparency logs.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::hash::{Digest, Rfc6962Fold};
+use crate::tree::TreeFold;
+
+/// The leaf ranges `(start, end)` of the subtrees whose hashes form the
+/// consistency proof between trees of `old_size` and `new_size` leaves, in
+/// proof order, following `SUBPROOF` in RFC 6962 section 2.1.2.
+pub fn consistency_ranges(old_size: usize, new_size: usize) -> Vec<(usize, usize)> {
+    fn subproof(m: usize, start: usize, end: usize, whole: bool, out: &mut Vec<(usize, usize)>) {
+        let n = end - start;
+        if m == n {
+            if !whole {
+                out.push((start, end));
+            }
+            return;
+        }
+        // the largest power of two less than n
+        let k = 1 << (usize::BITS - 1 - (n - 1).leading_zeros());
+        if m <= k {
+            subproof(m, start, start + k, whole, out);
+            out.push((start + k, end));
+        } else {
+            subproof(m - k, start + k, end, false, out);
+            out.push((start, start + k));
+        }
+    }
+
+    let mut ranges = vec![];
+    if old_size > 0 && old_size <= new_size {
+        subproof(old_size, 0, new_size, true, &mut ranges);
+    }
+    ranges
+}
+
+/// A proof that the tree of `old_size` leaves is a prefix of the tree of
+/// `new_size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsistencyProof<T: Clone> {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub nodes: Vec<T>,
+}
+
+impl<T: Clone> ConsistencyProof<T> {
+    pub fn new(old_size: usize, new_size: usize, nodes: Vec<T>) -> Self {
+        Self {
+            old_size,
+            new_size,
+            nodes,
+        }
+    }
+
+    /// Check the proof against both roots, following the verification
+    /// algorithm of RFC 9162 section 2.1.4.2.
+    pub fn verify<F>(&self, old_root: &T, new_root: &T, mut f: F) -> bool
+    where
+        T: PartialEq,
+        F: FnMut(T, T) -> T,
+    {
+        let (m, n) = (self.old_size, self.new_size);
+        if m == 0 || m > n {
+            return false;
+        }
+        if m == n {
+            return self.nodes.is_empty() && old_root == new_root;
+        }
+        let mut nodes = self.nodes.iter().cloned();
+        let first = if m.is_power_of_two() {
+            old_root.clone()
+        } else {
+            match nodes.next() {
+                Some(node) => node,
+                None => return false,
+            }
+        };
+        let (mut fnode, mut snode) = (m - 1, n - 1);
+        while fnode & 1 != 0 {
+            fnode >>= 1;
+            snode >>= 1;
+        }
+        let (mut fr, mut sr) = (first.clone(), first);
+        for node in nodes {
+            if snode == 0 {
+                return false;
+            }
+            if fnode & 1 != 0 || fnode == snode {
+                fr = f(node.clone(), fr);
+                sr = f(node, sr);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                sr = f(sr, node);
+            }
+            fnode >>= 1;
+            snode >>= 1;
+        }
+        snode == 0 && fr == *old_root && sr == *new_root
+    }
+}
+
+/// Verify a consistency proof between two roots of an RFC 6962 tree.
+pub fn verify_ct_consistency<H: Digest>(
+    proof: &ConsistencyProof<Vec<u8>>,
+    old_root: &[u8],
+    new_root: &[u8],
+) -> bool {
+    let mut hasher = Rfc6962Fold::<H, &[u8]>::new();
+    proof.verify(&old_root.to_vec(), &new_root.to_vec(), |l, r| {
+        hasher.fold(&l, &r).unwrap()
+    })
+}
+
+/// Collect a consistency proof while folding the leaves of the newer tree.
+///
+/// The sizes of both trees must be known in advance. The tree must not be
+/// filled, as the proof describes the unfilled shape.
+pub struct ConsistencyTracker<T: TreeFold> {
+    base: T,
+    old_size: usize,
+    new_size: usize,
+    input_index: usize,
+    stack: Vec<(usize, usize)>,
+    ranges: Vec<(usize, usize)>,
+    nodes: Vec<Option<T::Target>>,
+    fill: bool,
+}
+
+impl<T: TreeFold> ConsistencyTracker<T> {
+    pub fn new(base: T, old_size: usize, new_size: usize) -> Self {
+        let ranges = consistency_ranges(old_size, new_size);
+        Self {
+            base,
+            old_size,
+            new_size,
+            input_index: 0,
+            stack: vec![],
+            nodes: vec![None; ranges.len()],
+            ranges,
+            fill: false,
+        }
+    }
+
+    /// The completed proof, once every leaf of the newer tree has been
+    /// folded and the root taken.
+    pub fn proof_result(&self) -> Option<ConsistencyProof<T::Target>> {
+        if self.fill || self.old_size == 0 || self.old_size > self.new_size {
+            return None;
+        }
+        let nodes = self.nodes.iter().cloned().collect::<Option<Vec<_>>>()?;
+        Some(ConsistencyProof::new(self.old_size, self.new_size, nodes))
+    }
+
+    fn record(&mut self, range: (usize, usize), node: &T::Target) {
+        self.stack.push(range);
+        if let Some(pos) = self.ranges.iter().position(|r| *r == range) {
+            self.nodes[pos].replace(node.clone());
+        }
+    }
+}
+
+impl<T: TreeFold> TreeFold for ConsistencyTracker<T> {
+    type Leaf = T::Leaf;
+    type Target = T::Target;
+    type Error = T::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let r = self.base.input(leaf)?;
+        if !self.fill {
+            let index = self.input_index;
+            self.record((index, index + 1), &r);
+            self.input_index += 1;
+        }
+        Ok(r)
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        let r = self.base.fold(a, b)?;
+        if !self.fill {
+            if let (Some(right), Some(left)) = (self.stack.pop(), self.stack.pop()) {
+                self.record((left.0, right.1), &r);
+            }
+        }
+        Ok(r)
+    }
+
+    fn start_fill(&mut self) {
+        // filled trees are not tracked
+        self.fill = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::test::TestFold;
+    use crate::tree::TreeFolder;
+    use alloc::string::{String, ToString};
+    use sha2::Sha256;
+
+    fn test_fold(l: String, r: String) -> String {
+        format!("[{},{}]", l, r)
+    }
+
+    fn root(leaves: &[String]) -> String {
+        TreeFolder::fold(TestFold {}, leaves.to_vec(), None)
+            .unwrap()
+            .0
+            .unwrap()
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(consistency_ranges(3, 3), vec![]);
+        assert_eq!(consistency_ranges(4, 8), vec![(4, 8)]);
+        assert_eq!(
+            consistency_ranges(3, 7),
+            vec![(2, 3), (3, 4), (0, 2), (4, 7)]
+        );
+        assert_eq!(consistency_ranges(6, 8), vec![(4, 6), (6, 8), (0, 4)]);
+        assert_eq!(consistency_ranges(0, 8), vec![]);
+        assert_eq!(consistency_ranges(9, 8), vec![]);
+    }
+
+    #[test]
+    fn test_track() {
+        let leaves: Vec<String> = (0..20).map(|n| n.to_string()).collect();
+        for new_size in 1..=leaves.len() {
+            let new_root = root(&leaves[..new_size]);
+            for old_size in 1..=new_size {
+                let old_root = root(&leaves[..old_size]);
+                let tracker = ConsistencyTracker::new(TestFold {}, old_size, new_size);
+                let (result, tracker) =
+                    TreeFolder::fold(tracker, leaves[..new_size].to_vec(), None).unwrap();
+                assert_eq!(result.unwrap(), new_root);
+                let proof = tracker.proof_result().unwrap();
+                let expect: Vec<String> = consistency_ranges(old_size, new_size)
+                    .into_iter()
+                    .map(|(start, end)| root(&leaves[start..end]))
+                    .collect();
+                assert_eq!(proof.nodes, expect);
+                assert!(
+                    proof.verify(&old_root, &new_root, test_fold),
+                    "{} {}",
+                    old_size,
+                    new_size
+                );
+                assert!(!proof.verify(&new_root, &old_root, test_fold) || old_size == new_size);
+                if !proof.nodes.is_empty() {
+                    let mut tampered = proof.clone();
+                    tampered.nodes[0] = "X".to_string();
+                    assert!(!tampered.verify(&old_root, &new_root, test_fold));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_incomplete() {
+        let leaves: Vec<String> = (0..6).map(|n| n.to_string()).collect();
+        let tracker = ConsistencyTracker::new(TestFold {}, 3, 7);
+        let (_, tracker) = TreeFolder::fold(tracker, leaves.clone(), None).unwrap();
+        assert_eq!(tracker.proof_result(), None);
+
+        let tracker = ConsistencyTracker::new(TestFold {}, 3, 8);
+        let (_, tracker) = TreeFolder::fold(tracker, leaves, Some("E".to_string())).unwrap();
+        assert_eq!(tracker.proof_result(), None);
+    }
+
+    #[test]
+    fn test_ct_vectors() {
+        // the test leaves and proofs used by Certificate Transparency
+        let leaves: Vec<Vec<u8>> = [
+            "",
+            "00",
+            "10",
+            "2021",
+            "3031",
+            "40414243",
+            "5051525354555657",
+            "606162636465666768696a6b6c6d6e6f",
+        ]
+        .iter()
+        .map(|l| hex::decode(l).unwrap())
+        .collect();
+        let ct_root = |size: usize| {
+            TreeFolder::fold(
+                Rfc6962Fold::<Sha256, Vec<u8>>::new(),
+                leaves[..size].to_vec(),
+                None,
+            )
+            .unwrap()
+            .0
+            .unwrap()
+        };
+        assert_eq!(
+            hex::encode(ct_root(8)),
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328"
+        );
+
+        for (old_size, new_size, expect) in &[
+            (
+                1,
+                8,
+                &[
+                    "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+                ][..],
+            ),
+            (
+                6,
+                8,
+                &[
+                    "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+                    "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                    "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+                ][..],
+            ),
+            (
+                2,
+                5,
+                &[
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+                ][..],
+            ),
+        ] {
+            let tracker = ConsistencyTracker::new(
+                Rfc6962Fold::<Sha256, Vec<u8>>::new(),
+                *old_size,
+                *new_size,
+            );
+            let (new_root, tracker) =
+                TreeFolder::fold(tracker, leaves[..*new_size].to_vec(), None).unwrap();
+            let proof = tracker.proof_result().unwrap();
+            let nodes: Vec<String> = proof.nodes.iter().map(hex::encode).collect();
+            assert_eq!(nodes, *expect);
+            let old_root = ct_root(*old_size);
+            assert!(verify_ct_consistency::<Sha256>(
+                &proof,
+                &old_root,
+                &new_root.unwrap()
+            ));
+        }
+    }
+}
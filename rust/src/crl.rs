@@ -0,0 +1,185 @@
+//! Revoked certificates from X.509 certificate revocation lists.
+//!
+//! Revoked serial numbers are mapped onto registry indices, either directly
+//! for small integer serials or through a caller-supplied mapping, and the
+//! sorted indices are fed to a `BitSink` such as a `RangeParser`.
+
+use std::borrow::Cow;
+
+use x509_parser::pem::parse_x509_pem;
+use x509_parser::prelude::FromDer;
+use x509_parser::revocation_list::CertificateRevocationList;
+
+use crate::error::Error;
+use crate::input::{process_revoked_indices, BitSink};
+
+fn crl_der(data: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    let start = data.iter().position(|b| !b.is_ascii_whitespace());
+    if start.map(|pos| data[pos..].starts_with(b"-----BEGIN")) == Some(true) {
+        let (_, pem) =
+            parse_x509_pem(data).map_err(|err| Error::InvalidInput(format!("CRL: {}", err)))?;
+        if pem.label != "X509 CRL" {
+            return Err(Error::InvalidInput(format!(
+                "CRL: unexpected PEM label {}",
+                pem.label
+            )));
+        }
+        Ok(Cow::Owned(pem.contents))
+    } else {
+        Ok(Cow::Borrowed(data))
+    }
+}
+
+/// The serial numbers of the revoked certificates in a DER or PEM encoded
+/// CRL, as big-endian bytes without leading zeros. Negative serials are
+/// rejected.
+pub fn crl_serials(data: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let der = crl_der(data)?;
+    let (_, crl) = CertificateRevocationList::from_der(&der)
+        .map_err(|err| Error::InvalidInput(format!("CRL: {}", err)))?;
+    crl.iter_revoked_certificates()
+        .map(|revoked| {
+            let serial = revoked.raw_serial();
+            // a DER INTEGER with the high bit set is negative
+            if serial.first().is_some_and(|b| b & 0x80 != 0) {
+                return Err(Error::InvalidInput(format!(
+                    "CRL: negative serial: {}",
+                    hex_serial(serial)
+                )));
+            }
+            let start = serial.iter().position(|b| *b != 0).unwrap_or(serial.len());
+            Ok(serial[start..].to_vec())
+        })
+        .collect()
+}
+
+/// Use a serial number directly as the registry index, when it is an
+/// integer between 1 and `u32::MAX - 1`.
+pub fn serial_index(serial: &[u8]) -> Option<u32> {
+    let start = serial.iter().position(|b| *b != 0)?;
+    let serial = &serial[start..];
+    if serial.len() > 4 {
+        return None;
+    }
+    let index = serial.iter().fold(0u32, |acc, b| acc << 8 | *b as u32);
+    if index == u32::MAX {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+/// The sorted registry indices of the revoked certificates in a CRL.
+///
+/// Every revoked serial must be mapped, as an unmapped serial would
+/// otherwise appear to be valid.
+pub fn crl_indices<F>(data: &[u8], mut map: F) -> Result<Vec<u32>, Error>
+where
+    F: FnMut(&[u8]) -> Option<u32>,
+{
+    let mut indices = crl_serials(data)?
+        .iter()
+        .map(|serial| {
+            map(serial).ok_or_else(|| {
+                Error::InvalidInput(format!("unmapped serial: {}", hex_serial(serial)))
+            })
+        })
+        .collect::<Result<Vec<u32>, Error>>()?;
+    indices.sort_unstable();
+    indices.dedup();
+    Ok(indices)
+}
+
+/// Feed the revoked certificates in a CRL to a `BitSink`.
+pub fn process_crl<F, T>(data: &[u8], map: F, proc: T) -> Result<T::Result, Error>
+where
+    F: FnMut(&[u8]) -> Option<u32>,
+    T: BitSink,
+{
+    process_revoked_indices(crl_indices(data, map)?, proc)
+}
+
+fn hex_serial(serial: &[u8]) -> String {
+    serial.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::{RangeParser, RangeQueue};
+    use std::collections::HashMap;
+
+    // CRLs issued with openssl, revoking serials 02, 05, 06 and 0100, and
+    // serials 03 and 7F3A9C21D5E04B8612AA (too long to use as an index)
+    const CRL_DER: &[u8] = include_bytes!("../testdata/crl.der");
+    const CRL_PEM: &[u8] = include_bytes!("../testdata/crl.pem");
+    const CRL_LONG_SERIAL_PEM: &[u8] = include_bytes!("../testdata/crl-long-serial.pem");
+
+    #[test]
+    fn test_serial_index() {
+        assert_eq!(serial_index(&[0x00, 0x80]), Some(128));
+        assert_eq!(serial_index(&[0x01, 0x00, 0x00, 0x00]), Some(1 << 24));
+        assert_eq!(serial_index(&[0xff, 0xff, 0xff, 0xff]), None);
+        assert_eq!(serial_index(&[0x01, 0x00, 0x00, 0x00, 0x00]), None);
+        assert_eq!(serial_index(&[]), None);
+    }
+
+    #[test]
+    fn test_der_and_pem() {
+        let expected = vec![vec![2], vec![5], vec![6], vec![1, 0]];
+        assert_eq!(crl_serials(CRL_DER).unwrap(), expected);
+        assert_eq!(crl_serials(CRL_PEM).unwrap(), expected);
+
+        let collect = process_crl(
+            CRL_PEM,
+            serial_index,
+            RangeParser::new(RangeQueue::default()),
+        )
+        .unwrap();
+        assert_eq!(
+            collect.ranges,
+            vec![(0, 2), (2, 5), (6, 256), (256, u32::MAX)]
+        );
+    }
+
+    #[test]
+    fn test_mapping() {
+        assert!(matches!(
+            crl_indices(CRL_LONG_SERIAL_PEM, serial_index),
+            Err(Error::InvalidInput(_))
+        ));
+
+        let mut mapping = HashMap::new();
+        mapping.insert(vec![0x03], 10);
+        mapping.insert(hex::decode("7f3a9c21d5e04b8612aa").unwrap(), 4);
+        let map = |serial: &[u8]| mapping.get(serial).copied();
+        assert_eq!(crl_indices(CRL_LONG_SERIAL_PEM, map).unwrap(), vec![4, 10]);
+        let collect = process_crl(
+            CRL_LONG_SERIAL_PEM,
+            map,
+            RangeParser::new(RangeQueue::default()),
+        )
+        .unwrap();
+        assert_eq!(collect.ranges, vec![(0, 4), (4, 10), (10, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            crl_serials(b"not a crl"),
+            Err(Error::InvalidInput(_))
+        ));
+        let cert = b"-----BEGIN CERTIFICATE-----\nMA==\n-----END CERTIFICATE-----\n";
+        assert!(matches!(crl_serials(cert), Err(Error::InvalidInput(_))));
+
+        // serial 05 altered to 85, the encoding of -123
+        let mut der = CRL_DER.to_vec();
+        let pos = der
+            .windows(5)
+            .position(|w| w == [0x30, 0x12, 0x02, 0x01, 0x05])
+            .unwrap();
+        der[pos + 4] = 0x85;
+        assert!(matches!(crl_serials(&der), Err(Error::InvalidInput(_))));
+        assert!(process_crl(&der, serial_index, RangeParser::new(RangeQueue::default())).is_err());
+    }
+}
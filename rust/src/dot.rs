@@ -0,0 +1,306 @@
+//! Rendering of small range trees in the Graphviz DOT language.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use crate::error::Error;
+use crate::hash::{Digest, HashFold};
+use crate::input::process_bitmap;
+use crate::path::{Path, PathJoin};
+use crate::range::{make_range, range_path_hasher, RangeParser, RangeQueue, RangeTreeFolder};
+use crate::tree::{NodeVisitor, TreeFold};
+
+/// The number of hex digits shown for each interior node.
+const HASH_DIGITS: usize = 8;
+
+/// The level and position of a node.
+type NodeKey = (usize, usize);
+
+/// Collects the nodes of a tree, by level and position, for rendering.
+#[derive(Clone, Debug, Default)]
+pub struct DotTree {
+    nodes: BTreeMap<NodeKey, (Vec<u8>, bool)>,
+}
+
+impl<N: AsRef<[u8]>> NodeVisitor<N> for DotTree {
+    fn visit(&mut self, level: usize, position: usize, node: &N, fill: bool) {
+        self.nodes
+            .insert((level, position), (node.as_ref().to_vec(), fill));
+    }
+}
+
+impl DotTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn children(&self, level: usize, position: usize) -> Option<(NodeKey, NodeKey)> {
+        if level == 0 {
+            return None;
+        }
+        let left = (level - 1, position * 2);
+        // the right child of a join between peaks may be lower in the tree
+        let mid = (position << level) + (1 << (level - 1));
+        let right = (0..level)
+            .rev()
+            .map(|k| (k, mid >> k))
+            .find(|(k, pos)| pos << k == mid && self.nodes.contains_key(&(*k, *pos)))?;
+        Some((left, right))
+    }
+
+    /// The nodes along a path from its leaf, and the siblings joined with
+    /// them.
+    fn path_nodes(&self, path: &Path<Vec<u8>>) -> (BTreeSet<NodeKey>, BTreeSet<NodeKey>) {
+        let mut nodes = BTreeSet::new();
+        let mut siblings = BTreeSet::new();
+        let leaf = self
+            .nodes
+            .iter()
+            .find(|((level, _), (hash, fill))| *level == 0 && !fill && *hash == path.leaf);
+        let mut current = match leaf {
+            Some((key, _)) => *key,
+            None => return (nodes, siblings),
+        };
+        nodes.insert(current);
+        let parents: BTreeMap<NodeKey, (NodeKey, NodeKey)> = self
+            .nodes
+            .keys()
+            .filter_map(|(level, pos)| self.children(*level, *pos).map(|c| ((*level, *pos), c)))
+            .flat_map(|(parent, (left, right))| [(left, (parent, right)), (right, (parent, left))])
+            .collect();
+        for join in &path.join {
+            let (parent, sibling) = match parents.get(&current) {
+                Some(found) => *found,
+                None => break,
+            };
+            let hash = match join {
+                PathJoin::Left(hash) | PathJoin::Right(hash) => hash,
+            };
+            if self.nodes.get(&sibling).map(|(h, _)| h) != Some(hash) {
+                break;
+            }
+            siblings.insert(sibling);
+            nodes.insert(parent);
+            current = parent;
+        }
+        (nodes, siblings)
+    }
+
+    /// Render as a DOT digraph, labelling leaves with their ranges and
+    /// other nodes with truncated hashes, and drawing fill nodes dashed. The
+    /// nodes of `highlight` and their siblings are shaded.
+    pub fn render(&self, ranges: &[(u32, u32)], highlight: Option<&Path<Vec<u8>>>) -> String {
+        let (on_path, siblings) = match highlight {
+            Some(path) => self.path_nodes(path),
+            None => (BTreeSet::new(), BTreeSet::new()),
+        };
+
+        // from the root downwards, and from left to right within each level
+        let mut keys: Vec<NodeKey> = self.nodes.keys().copied().collect();
+        keys.sort_by_key(|(level, pos)| (core::cmp::Reverse(*level), *pos));
+
+        let mut out = String::new();
+        out.push_str("digraph tree {\n");
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for (level, pos) in &keys {
+            let (hash, fill) = &self.nodes[&(*level, *pos)];
+            let label = match (level, fill) {
+                (0, true) => String::from("fill"),
+                (0, false) => match ranges.get(*pos) {
+                    Some((left, right)) => format!("({}, {})", left, right),
+                    None => hex_prefix(hash),
+                },
+                _ => hex_prefix(hash),
+            };
+            let mut styles = Vec::new();
+            if *fill {
+                styles.push("dashed");
+            }
+            let mut attrs = format!("label=\"{}\"", label);
+            if on_path.contains(&(*level, *pos)) {
+                styles.push("filled");
+                attrs.push_str(", fillcolor=lightblue");
+            } else if siblings.contains(&(*level, *pos)) {
+                styles.push("filled");
+                attrs.push_str(", fillcolor=lightyellow");
+            }
+            if *fill {
+                attrs.push_str(", color=gray, fontcolor=gray40");
+            }
+            if !styles.is_empty() {
+                write!(attrs, ", style=\"{}\"", styles.join(",")).unwrap();
+            }
+            writeln!(out, "  n{}_{} [{}];", level, pos, attrs).unwrap();
+        }
+        for (level, pos) in &keys {
+            if let Some((left, right)) = self.children(*level, *pos) {
+                for child in &[left, right] {
+                    let bold = on_path.contains(&(*level, *pos)) && on_path.contains(child);
+                    write!(out, "  n{}_{} -> n{}_{}", level, pos, child.0, child.1).unwrap();
+                    out.push_str(if bold { " [penwidth=2];\n" } else { ";\n" });
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn hex_prefix(hash: &[u8]) -> String {
+    let mut out = String::new();
+    for b in hash.iter().take(HASH_DIGITS.div_ceil(2)) {
+        write!(out, "{:02x}", b).unwrap();
+    }
+    out.truncate(HASH_DIGITS);
+    out
+}
+
+/// Render the tree built by `base` over a sequence of ranges.
+pub fn render_dot<F>(
+    base: F,
+    ranges: &[(u32, u32)],
+    fill: bool,
+    highlight: Option<&Path<Vec<u8>>>,
+) -> Result<String, F::Error>
+where
+    F: TreeFold<Leaf = [u8; 8], Target = Vec<u8>>,
+{
+    let mut folder = RangeTreeFolder::with_visitor(base, DotTree::new());
+    for (left, right) in ranges {
+        folder.folder.push(&make_range(*left, *right))?;
+    }
+    if fill {
+        folder.folder.fill(make_range(u32::MAX, u32::MAX))?;
+    }
+    let (_, _, tree) = folder.folder.finish()?;
+    Ok(tree.render(ranges, highlight))
+}
+
+/// Render the hash tree of a bitmap, highlighting the path for a
+/// non-revoked index if given.
+pub fn bitmap_dot<H: Digest>(
+    bitmap: &[u8],
+    fill: bool,
+    index: Option<u32>,
+) -> Result<String, Error> {
+    let ranges: Vec<(u32, u32)> = process_bitmap(bitmap, RangeParser::new(RangeQueue::default()))?
+        .ranges
+        .into_iter()
+        .collect();
+    let path = match index {
+        Some(index) => {
+            let mut tracker =
+                process_bitmap(bitmap, RangeParser::new(range_path_hasher::<H>(index)))?;
+            if fill {
+                tracker.fill();
+            }
+            let (_, path, _) = tracker.result();
+            path
+        }
+        None => None,
+    };
+    Ok(render_dot(
+        HashFold::<H, [u8; 8]>::new(),
+        &ranges,
+        fill,
+        path.as_ref(),
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_unfilled() {
+        // five ranges: the root joins a peak of four with a single leaf
+        let dot = bitmap_dot::<Sha256>(&[0x55], false, None).unwrap();
+        assert!(dot.starts_with("digraph tree {\n"));
+        assert!(dot.contains("n0_0 [label=\"(0, 2)\"];"));
+        assert!(dot.contains("n0_4 [label=\"(8, 4294967295)\"];"));
+        assert!(dot.contains("n3_0 -> n2_0;\n  n3_0 -> n0_4;"));
+        assert!(!dot.contains("fill"));
+        assert_eq!(dot.matches(" -> ").count(), 8);
+    }
+
+    #[test]
+    fn test_filled() {
+        let dot = bitmap_dot::<Sha256>(&[0x55], true, None).unwrap();
+        assert_eq!(dot.matches("label=\"fill\"").count(), 3);
+        assert!(dot.contains("n1_3 -> n0_6;\n  n1_3 -> n0_7;"));
+        assert!(dot.contains("n1_3 [label=\""));
+        assert_eq!(dot.matches("style=\"dashed\"").count(), 4);
+        assert_eq!(dot.matches(" -> ").count(), 14);
+    }
+
+    struct FailFold {
+        fail_at: usize,
+        folds: usize,
+    }
+
+    impl TreeFold for FailFold {
+        type Leaf = [u8; 8];
+        type Target = Vec<u8>;
+        type Error = String;
+
+        fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+            Ok(leaf.to_vec())
+        }
+
+        fn fold(
+            &mut self,
+            a: &Self::Target,
+            b: &Self::Target,
+        ) -> Result<Self::Target, Self::Error> {
+            self.folds += 1;
+            if self.folds == self.fail_at {
+                return Err(String::from("failed"));
+            }
+            Ok([&a[..4], &b[..4]].concat())
+        }
+    }
+
+    #[test]
+    fn test_fold_error() {
+        let ranges = [(0, 2), (2, 4), (4, 6)];
+        // one fold while pushing, one in the fill and one more to join it
+        for fail_at in 1..=3 {
+            let fold = FailFold { fail_at, folds: 0 };
+            assert_eq!(
+                render_dot(fold, &ranges, true, None),
+                Err(String::from("failed"))
+            );
+        }
+        // without filling, the peaks are joined on finishing
+        let fold = FailFold {
+            fail_at: 2,
+            folds: 0,
+        };
+        assert!(render_dot(fold, &ranges, false, None).is_err());
+        let fold = FailFold {
+            fail_at: 4,
+            folds: 0,
+        };
+        assert!(render_dot(fold, &ranges, true, None).is_ok());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let dot = bitmap_dot::<Sha256>(&[0x55], true, Some(5)).unwrap();
+        // the leaf (4, 6) and its ancestors are highlighted
+        assert!(dot.contains("n0_2 [label=\"(4, 6)\", fillcolor=lightblue, style=\"filled\"];"));
+        assert_eq!(dot.matches("fillcolor=lightblue").count(), 4);
+        assert_eq!(dot.matches("fillcolor=lightyellow").count(), 3);
+        assert!(dot.contains("n1_1 -> n0_2 [penwidth=2];"));
+        assert!(dot.contains("n3_0 -> n2_0 [penwidth=2];"));
+        assert!(dot.contains("n3_0 -> n2_1;"));
+
+        // the path for a revoked index is not found
+        let dot = bitmap_dot::<Sha256>(&[0x55], true, Some(2)).unwrap();
+        assert!(!dot.contains("fillcolor"));
+    }
+}
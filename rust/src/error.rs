@@ -1,17 +1,43 @@
+#[cfg(feature = "std")]
 use std::io;
 
+#[cfg(feature = "std")]
 use thiserror::Error;
 
+#[cfg(feature = "std")]
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("input/output error")]
     Io(#[from] io::Error),
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
     #[error("unexpected error")]
     Unexpected,
+    #[error("operation cancelled")]
+    Cancelled,
 }
 
-impl From<std::convert::Infallible> for Error {
-    fn from(_err: std::convert::Infallible) -> Self {
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub enum Error {
+    InvalidInput(alloc::string::String),
+    Unexpected,
+    Cancelled,
+}
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidInput(msg) => write!(f, "invalid input: {}", msg),
+            Error::Unexpected => f.write_str("unexpected error"),
+            Error::Cancelled => f.write_str("operation cancelled"),
+        }
+    }
+}
+
+impl From<core::convert::Infallible> for Error {
+    fn from(_err: core::convert::Infallible) -> Self {
         Error::Unexpected
     }
 }
@@ -0,0 +1,225 @@
+//! Keccak-256 range trees compatible with Solidity Merkle proof verifiers.
+//!
+//! Leaves follow the OpenZeppelin `StandardMerkleTree` encoding, being
+//! `keccak256(bytes.concat(keccak256(abi.encode(uint32 left, uint32 right))))`,
+//! and interior nodes hash the sorted pair of child hashes as in
+//! `MerkleProof.verify`. Because pairs are sorted, proofs carry no directions.
+
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use sha3::{Digest, Keccak256};
+
+use crate::path::{Path, PathJoin};
+use crate::range::{RangePathTracker, RangeTreeFolder};
+use crate::tree::TreeFold;
+
+pub type EthHash = [u8; 32];
+
+fn keccak(data: &[u8]) -> EthHash {
+    Keccak256::digest(data).as_slice().try_into().unwrap()
+}
+
+fn abi_word(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Hash a pair of nodes in sorted order, as `MerkleProof` does.
+pub fn eth_hash_pair(a: &EthHash, b: &EthHash) -> EthHash {
+    let mut data = [0u8; 64];
+    if a <= b {
+        data[..32].copy_from_slice(a);
+        data[32..].copy_from_slice(b);
+    } else {
+        data[..32].copy_from_slice(b);
+        data[32..].copy_from_slice(a);
+    }
+    keccak(&data)
+}
+
+/// The leaf hash for a non-revoked range.
+pub fn eth_leaf_hash(left: u32, right: u32) -> EthHash {
+    let mut encoded = [0u8; 64];
+    encoded[..32].copy_from_slice(&abi_word(left as u64));
+    encoded[32..].copy_from_slice(&abi_word(right as u64));
+    keccak(&keccak(&encoded))
+}
+
+/// Compute a `function_selector` from a Solidity signature such as
+/// `"verify(bytes32[],uint32,uint32)"`.
+pub fn eth_selector(signature: &str) -> [u8; 4] {
+    keccak(signature.as_bytes())[..4].try_into().unwrap()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct EthFold;
+
+impl EthFold {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl TreeFold for EthFold {
+    type Leaf = [u8; 8];
+    type Target = EthHash;
+    type Error = core::convert::Infallible;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let left = u32::from_be_bytes(leaf[..4].try_into().unwrap());
+        let right = u32::from_be_bytes(leaf[4..].try_into().unwrap());
+        Ok(eth_leaf_hash(left, right))
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        Ok(eth_hash_pair(a, b))
+    }
+}
+
+pub fn eth_range_hasher() -> RangeTreeFolder<EthFold> {
+    RangeTreeFolder::new(EthFold::new())
+}
+
+pub fn eth_range_path_hasher(find_index: u32) -> RangePathTracker<EthFold> {
+    RangePathTracker::new(EthFold::new(), find_index)
+}
+
+/// A non-revocation proof in the form expected by an on-chain verifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthProof {
+    pub left: u32,
+    pub right: u32,
+    pub proof: Vec<EthHash>,
+}
+
+impl EthProof {
+    pub fn new(range: (u32, u32), path: &Path<EthHash>) -> Self {
+        let proof = path
+            .join
+            .iter()
+            .map(|part| match part {
+                PathJoin::Left(h) | PathJoin::Right(h) => *h,
+            })
+            .collect();
+        Self {
+            left: range.0,
+            right: range.1,
+            proof,
+        }
+    }
+
+    pub fn leaf(&self) -> EthHash {
+        eth_leaf_hash(self.left, self.right)
+    }
+
+    pub fn verify(&self, index: u32, root: &EthHash) -> bool {
+        if index <= self.left || index >= self.right {
+            return false;
+        }
+        let computed = self
+            .proof
+            .iter()
+            .fold(self.leaf(), |h, sibling| eth_hash_pair(&h, sibling));
+        computed == *root
+    }
+
+    /// ABI-encode the arguments `(bytes32[] proof, uint32 left, uint32 right)`.
+    pub fn abi_encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 * (4 + self.proof.len()));
+        out.extend_from_slice(&abi_word(3 * 32));
+        out.extend_from_slice(&abi_word(self.left as u64));
+        out.extend_from_slice(&abi_word(self.right as u64));
+        out.extend_from_slice(&abi_word(self.proof.len() as u64));
+        for node in &self.proof {
+            out.extend_from_slice(node);
+        }
+        out
+    }
+
+    /// Produce calldata for a function taking `(bytes32[], uint32, uint32)`.
+    pub fn calldata(&self, signature: &str) -> Vec<u8> {
+        let mut out = eth_selector(signature).to_vec();
+        out.extend(self.abi_encode());
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::process_bitmap;
+    use crate::range::RangeParser;
+
+    // Reference implementation of OpenZeppelin `MerkleProof.verifyCalldata`,
+    // operating on the ABI-encoded arguments
+    fn solidity_verify(calldata: &[u8], root: &EthHash, index: u32) -> bool {
+        let word = |pos: usize| -> &[u8] { &calldata[pos..pos + 32] };
+        let uint = |pos: usize| u64::from_be_bytes(word(pos)[24..].try_into().unwrap());
+        let offset = uint(0) as usize;
+        let left = uint(32) as u32;
+        let right = uint(64) as u32;
+        let count = uint(offset) as usize;
+        if index <= left || index >= right {
+            return false;
+        }
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(word(32));
+        encoded.extend_from_slice(word(64));
+        let mut computed = keccak(&keccak(&encoded));
+        for i in 0..count {
+            let sibling: EthHash = word(offset + 32 * (i + 1)).try_into().unwrap();
+            computed = if computed < sibling {
+                keccak(&[&computed[..], &sibling[..]].concat())
+            } else {
+                keccak(&[&sibling[..], &computed[..]].concat())
+            };
+        }
+        computed == *root
+    }
+
+    #[test]
+    fn test_known_hashes() {
+        // keccak256("")
+        assert_eq!(
+            keccak(b""),
+            [
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70
+            ]
+        );
+        assert_eq!(
+            eth_selector("transfer(address,uint256)"),
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+    }
+
+    #[test]
+    fn test_proofs() {
+        let mut bitmap = [0u8; 32];
+        bitmap[0] = 0x21;
+        bitmap[3] = 0x18;
+        bitmap[17] = 0xf0;
+        for index in 1..=256 {
+            let parser = RangeParser::new(eth_range_path_hasher(index));
+            let mut tracker = process_bitmap(&bitmap, parser).unwrap();
+            tracker.fill();
+            let (range, path, root) = tracker.result();
+            let root = root.unwrap();
+            let (range, path) = match (range, path) {
+                (Some(range), Some(path)) => (range, path),
+                _ => continue,
+            };
+            let proof = EthProof::new(range, &path);
+            assert_eq!(proof.leaf(), path.leaf);
+            assert!(proof.verify(index, &root));
+            assert!(solidity_verify(&proof.abi_encode(), &root, index));
+            assert!(!solidity_verify(&proof.abi_encode(), &root, range.1));
+
+            let calldata = proof.calldata("verify(bytes32[],uint32,uint32)");
+            assert_eq!(&calldata[4..], &proof.abi_encode()[..]);
+        }
+    }
+}
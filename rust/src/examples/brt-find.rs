@@ -3,17 +3,22 @@ use std::time::Instant;
 
 use sha2::Sha256;
 
-use brangetree::{find_merkle_path, Digest, Error, PathJoin};
+use brangetree::{find_merkle_path, find_merkle_path_unfilled, Digest, Error, PathJoin};
 
 fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().skip(1).take(2).collect();
-    if args.len() != 2 {
-        println!("Expected two arguments: path and index");
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let fill = !flags.iter().any(|flag| flag == "--no-fill");
+    if args.len() != 2 || flags.iter().any(|flag| flag != "--no-fill") {
+        println!("Expected two arguments: path and index, and optionally --no-fill");
     } else {
         let index = args[1].parse::<u32>().unwrap();
         let start = Instant::now();
-        let (found_range, found_path, mut result) =
-            find_merkle_path::<Sha256>(args[0].clone(), index)?;
+        let (found_range, found_path, mut result) = if fill {
+            find_merkle_path::<Sha256>(args[0].clone(), index)?
+        } else {
+            find_merkle_path_unfilled::<Sha256>(args[0].clone(), index)?
+        };
         let dur = Instant::now() - start;
 
         if let Some(root) = result.root.take() {
@@ -42,6 +47,9 @@ fn main() -> Result<(), Error> {
             }
             println!("root hash    {}", hex::encode(root));
             println!("leaf count:  {}", result.leaf_count);
+            if fill {
+                println!("filled:      {}", result.filled_count);
+            }
             println!("duration:    {:0.3}", dur.as_secs_f64());
         } else {
             println!("{} no hash produced", args[0]);
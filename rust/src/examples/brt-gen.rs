@@ -0,0 +1,56 @@
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use brangetree::gen::{generate_registry, Distribution};
+use brangetree::Error;
+
+const USAGE: &str = "Usage: brt-gen [--seed N] [--dist uniform|clustered[:N]|prefix|alternating] \
+                     SIZE_BITS PERCENT [OUTPUT]";
+
+fn main() -> Result<(), Error> {
+    let mut seed = 0u64;
+    let mut dist = Distribution::Uniform;
+    let mut positional = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = args
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| Error::InvalidInput("expected a numeric seed".to_string()))?
+            }
+            "--dist" => {
+                dist = args
+                    .next()
+                    .ok_or_else(|| Error::InvalidInput("expected a distribution".to_string()))?
+                    .parse()?
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() < 2 || positional.len() > 3 {
+        return Err(Error::InvalidInput(USAGE.to_string()));
+    }
+
+    let index_bits = positional[0]
+        .parse::<u32>()
+        .ok()
+        .filter(|bits| *bits < 32)
+        .ok_or_else(|| Error::InvalidInput(format!("invalid size: {}", positional[0])))?;
+    let percent = positional[1]
+        .parse::<f64>()
+        .map_err(|_| Error::InvalidInput(format!("invalid percentage: {}", positional[1])))?;
+    let path = positional.get(2).cloned().unwrap_or_else(|| {
+        let name = dist.to_string().replace(':', "-");
+        format!("{}bits_{}pc_{}_{}.gz", index_bits, percent, name, seed)
+    });
+
+    let registry = generate_registry(1 << index_bits, percent / 100.0, seed, dist)?;
+    registry
+        .save(BufWriter::new(File::create(&path)?))?
+        .flush()?;
+    println!("{} {}", path, registry.revoked_count());
+    Ok(())
+}
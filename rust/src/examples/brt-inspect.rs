@@ -0,0 +1,54 @@
+use std::env;
+
+use naturalize::to_natural;
+
+use brangetree::{inspect_zipped, Error};
+
+fn natural_sort<I: IntoIterator<Item = String>>(paths: I) -> Vec<String> {
+    let mut paths: Vec<(String, String)> = paths
+        .into_iter()
+        .map(|arg| (to_natural(&arg).unwrap(), arg))
+        .collect();
+    paths.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    paths.into_iter().map(|a| a.1).collect()
+}
+
+fn main() -> Result<(), Error> {
+    let paths: Vec<String> = natural_sort(env::args().skip(1));
+    if paths.is_empty() {
+        println!("Expected one or more filenames");
+    }
+    for path in paths {
+        let stats = inspect_zipped(path.clone())?;
+        let percent = if stats.total_bits > 0 {
+            stats.revoked as f64 * 100.0 / stats.total_bits as f64
+        } else {
+            0.0
+        };
+        println!("{}", path);
+        println!("  total bits:    {}", stats.total_bits);
+        println!("  revoked:       {} ({:0.2}%)", stats.revoked, percent);
+        println!("  leaves:        {}", stats.leaf_count);
+        println!("  filled leaves: {}", stats.filled_count);
+        println!("  depth:         {}", stats.depth);
+        println!("  filled depth:  {}", stats.filled_depth);
+        println!("  longest run:   {}", stats.longest_run);
+        match stats.shortest_run {
+            Some(run) => println!("  shortest run:  {}", run),
+            None => println!("  shortest run:  -"),
+        }
+        println!("  proof size:    {} bytes", stats.proof_size(32));
+        println!("  run lengths:");
+        for (bucket, count) in stats.run_histogram.iter().enumerate() {
+            if *count > 0 {
+                println!(
+                    "    {:>10}..{:<10} {}",
+                    1u64 << bucket,
+                    (1u64 << (bucket + 1)) - 1,
+                    count
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,46 +1,20 @@
 use std::env;
 use std::time::Instant;
 
-use generic_array::typenum::U2;
+use generic_array::typenum::{U11, U2, U4, U8};
 use lazy_static::lazy_static;
 use naturalize::to_natural;
-use neptune::poseidon::{HashMode, Poseidon, PoseidonConstants};
-use neptune::scalar_from_u64;
+use neptune::poseidon::{Arity, PoseidonConstants};
 use paired::bls12_381::{Bls12, Fr};
 
-use brangetree::{process_zipped_bits, Error, RangeParser, RangeTreeFolder, TreeFold};
+use brangetree::poseidon::PoseidonFold;
+use brangetree::{process_zipped_bits, Error, RangeKTreeFolder, RangeParser};
 
 lazy_static! {
-    static ref CONSTANTS: PoseidonConstants<Bls12, U2> = PoseidonConstants::new();
-}
-
-pub struct PHashFold<'a> {
-    hasher: Poseidon<'a, Bls12>,
-}
-
-impl PHashFold<'_> {
-    pub fn new() -> Self {
-        Self {
-            hasher: Poseidon::new(&CONSTANTS),
-        }
-    }
-}
-
-impl TreeFold for PHashFold<'_> {
-    type Leaf = [u8; 8];
-    type Target = Fr;
-    type Error = std::convert::Infallible;
-
-    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
-        Ok(scalar_from_u64(u64::from_be_bytes(*leaf)))
-    }
-
-    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
-        self.hasher.reset();
-        self.hasher.input(*a).unwrap();
-        self.hasher.input(*b).unwrap();
-        Ok(self.hasher.hash_in_mode(HashMode::OptimizedStatic))
-    }
+    static ref CONSTANTS_2: PoseidonConstants<Bls12, U2> = PoseidonConstants::new();
+    static ref CONSTANTS_4: PoseidonConstants<Bls12, U4> = PoseidonConstants::new();
+    static ref CONSTANTS_8: PoseidonConstants<Bls12, U8> = PoseidonConstants::new();
+    static ref CONSTANTS_11: PoseidonConstants<Bls12, U11> = PoseidonConstants::new();
 }
 
 pub struct PHashResult {
@@ -49,8 +23,12 @@ pub struct PHashResult {
     pub root: Option<Fr>,
 }
 
-pub fn hash_zipped(path: String, fill: bool) -> Result<PHashResult, Error> {
-    let target = RangeTreeFolder::new(PHashFold::new());
+pub fn hash_zipped<A: Arity<Fr>>(
+    path: String,
+    constants: &PoseidonConstants<Bls12, A>,
+    fill: bool,
+) -> Result<PHashResult, Error> {
+    let target = RangeKTreeFolder::new(PoseidonFold::new(constants), A::to_usize());
     let mut parsed = process_zipped_bits(path, RangeParser::new(target))?;
     let leaf_count = parsed.len();
     let filled_count = if fill {
@@ -77,10 +55,29 @@ fn natural_sort<I: IntoIterator<Item = String>>(paths: I) -> Vec<String> {
 }
 
 fn main() -> Result<(), Error> {
-    let paths: Vec<String> = natural_sort(env::args().skip(1));
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut arity = 2;
+    if args.len() > 1 && args[0] == "--arity" {
+        arity = args[1]
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidInput(format!("invalid arity: {}", args[1])))?;
+        args.drain(..2);
+    }
+    let paths: Vec<String> = natural_sort(args);
     for path in paths {
         let start = Instant::now();
-        let mut result = hash_zipped(path.clone(), true)?;
+        let mut result = match arity {
+            2 => hash_zipped(path.clone(), &CONSTANTS_2, true)?,
+            4 => hash_zipped(path.clone(), &CONSTANTS_4, true)?,
+            8 => hash_zipped(path.clone(), &CONSTANTS_8, true)?,
+            11 => hash_zipped(path.clone(), &CONSTANTS_11, true)?,
+            _ => {
+                return Err(Error::InvalidInput(format!(
+                    "unsupported arity {}, expected 2, 4, 8 or 11",
+                    arity
+                )))
+            }
+        };
         let dur = Instant::now() - start;
 
         if let Some(root) = result.root.take() {
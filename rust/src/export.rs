@@ -0,0 +1,239 @@
+//! Writers for the nodes reported to a `NodeVisitor`, for building external
+//! indexes over every level of a tree.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::tree::NodeVisitor;
+
+/// Write every node to a single stream as a record of the level (u32) and
+/// position (u64), both big-endian, a byte which is 1 for fill nodes, and
+/// the node itself.
+pub struct NodeWriter<W: Write> {
+    target: W,
+    count: u64,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> NodeWriter<W> {
+    pub fn new(target: W) -> Self {
+        Self {
+            target,
+            count: 0,
+            error: None,
+        }
+    }
+
+    /// The number of nodes written.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Flush the stream, returning the first error encountered.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+        self.target.flush()?;
+        Ok(self.target)
+    }
+}
+
+impl<W: Write, N: AsRef<[u8]>> NodeVisitor<N> for NodeWriter<W> {
+    fn visit(&mut self, level: usize, position: usize, node: &N, fill: bool) {
+        if self.error.is_some() {
+            return;
+        }
+        let mut header = [0u8; 13];
+        header[..4].copy_from_slice(&(level as u32).to_be_bytes());
+        header[4..12].copy_from_slice(&(position as u64).to_be_bytes());
+        header[12] = fill as u8;
+        let res = self
+            .target
+            .write_all(&header)
+            .and_then(|_| self.target.write_all(node.as_ref()));
+        match res {
+            Ok(()) => self.count += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+/// Write the nodes of each level to a separate stream, opened on demand, so
+/// that with fixed-size nodes the node at `position` begins at offset
+/// `position * size`.
+pub struct LevelWriter<W: Write, F: FnMut(usize) -> io::Result<W>> {
+    open: F,
+    levels: Vec<(W, usize)>,
+    error: Option<Error>,
+}
+
+impl<W: Write, F: FnMut(usize) -> io::Result<W>> LevelWriter<W, F> {
+    pub fn new(open: F) -> Self {
+        Self {
+            open,
+            levels: vec![],
+            error: None,
+        }
+    }
+
+    fn write(&mut self, level: usize, position: usize, node: &[u8]) -> Result<(), Error> {
+        while self.levels.len() <= level {
+            let target = (self.open)(self.levels.len())?;
+            self.levels.push((target, 0));
+        }
+        let (target, next) = &mut self.levels[level];
+        if position != *next {
+            return Err(Error::InvalidInput(format!(
+                "expected position {} at level {}, got {}",
+                next, level, position
+            )));
+        }
+        target.write_all(node)?;
+        *next += 1;
+        Ok(())
+    }
+
+    /// Flush the streams, returning them from the lowest level, or the first
+    /// error encountered.
+    pub fn finish(mut self) -> Result<Vec<W>, Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let mut levels = Vec::with_capacity(self.levels.len());
+        for (mut target, _) in self.levels {
+            target.flush()?;
+            levels.push(target);
+        }
+        Ok(levels)
+    }
+}
+
+impl<W, F, N> NodeVisitor<N> for LevelWriter<W, F>
+where
+    W: Write,
+    F: FnMut(usize) -> io::Result<W>,
+    N: AsRef<[u8]>,
+{
+    fn visit(&mut self, level: usize, position: usize, node: &N, _fill: bool) {
+        if self.error.is_none() {
+            if let Err(err) = self.write(level, position, node.as_ref()) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// The path of the file holding a level written by `level_files`.
+pub fn level_path<P: AsRef<Path>>(dir: P, level: usize) -> PathBuf {
+    dir.as_ref().join(format!("level-{}.bin", level))
+}
+
+pub type LevelFileWriter =
+    LevelWriter<BufWriter<File>, Box<dyn FnMut(usize) -> io::Result<BufWriter<File>>>>;
+
+/// A `LevelWriter` creating a file for each level within a directory.
+pub fn level_files<P: AsRef<Path>>(dir: P) -> Result<LevelFileWriter, Error> {
+    let dir = dir.as_ref().to_path_buf();
+    fs::create_dir_all(&dir)?;
+    Ok(LevelWriter::new(Box::new(move |level| {
+        Ok(BufWriter::new(File::create(level_path(&dir, level))?))
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hash::HashFold;
+    use crate::input::process_bitmap;
+    use crate::range::{range_hasher, RangeParser, RangeTreeFolder};
+    use crate::tree::test::TestFold;
+    use crate::tree::TreeFolder;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_visit_levels() {
+        for leaf_count in 1..40usize {
+            for fill in &[None, Some("E".to_string())] {
+                let mut nodes = vec![];
+                let mut folder = TreeFolder::with_visitor(
+                    TestFold {},
+                    |level: usize, position: usize, node: &String, fill: bool| {
+                        nodes.push((level, position, node.clone(), fill))
+                    },
+                );
+                folder
+                    .extend((0..leaf_count).map(|n| n.to_string()))
+                    .unwrap();
+                if let Some(fill) = fill {
+                    folder.fill(fill.clone()).unwrap();
+                }
+                let (root, _, _) = folder.finish().unwrap();
+
+                // every level is reported left to right without gaps
+                let mut next = vec![];
+                for (level, position, _, _) in &nodes {
+                    if next.len() <= *level {
+                        next.resize(level + 1, 0);
+                    }
+                    assert_eq!(*position, next[*level], "{} {:?}", leaf_count, fill);
+                    next[*level] += 1;
+                }
+                let (level, position, top, _) = nodes.last().unwrap();
+                assert_eq!((*position, Some(top.clone())), (0, root));
+                assert_eq!(level + 1, next.len());
+                if fill.is_some() {
+                    let width = leaf_count.next_power_of_two();
+                    assert_eq!(nodes.len(), width * 2 - 1);
+                    assert!(nodes
+                        .iter()
+                        .filter(|node| node.0 == 0 && node.1 >= leaf_count)
+                        .all(|node| node.3 && node.2 == "E"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_writer() {
+        let parser = RangeParser::new(RangeTreeFolder::with_visitor(
+            HashFold::<Sha256, [u8; 8]>::new(),
+            NodeWriter::new(vec![]),
+        ));
+        let mut hasher = process_bitmap(&[0x55], parser).unwrap();
+        hasher.fill();
+        let (root, _, writer) = hasher.finish();
+        assert_eq!(writer.count(), 15);
+        let out = writer.finish().unwrap();
+        assert_eq!(out.len(), 15 * (13 + 32));
+        let last = &out[out.len() - 45..];
+        assert_eq!(&last[..13], &[0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&last[13..], root.unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_level_files() {
+        let dir = std::env::temp_dir().join(format!("brt-levels-{}", std::process::id()));
+        let bitmap = [0x55, 0x0f, 0x81];
+        let writer = level_files(&dir).unwrap();
+        let parser = RangeParser::new(RangeTreeFolder::with_visitor(
+            HashFold::<Sha256, [u8; 8]>::new(),
+            writer,
+        ));
+        let (root, _, writer) = process_bitmap(&bitmap, parser).unwrap().finish();
+        writer.finish().unwrap();
+
+        let expect = process_bitmap(&bitmap, RangeParser::new(range_hasher::<Sha256>())).unwrap();
+        let leaves = fs::read(level_path(&dir, 0)).unwrap();
+        assert_eq!(leaves.len(), expect.len() * 32);
+        let top = (0..)
+            .map(|level| level_path(&dir, level))
+            .take_while(|path| path.exists())
+            .last()
+            .unwrap();
+        assert_eq!(fs::read(top).unwrap(), root.unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,440 @@
+use std::convert::TryInto;
+use std::panic::{catch_unwind, UnwindSafe};
+use std::slice;
+
+use sha2::Sha256;
+
+use crate::error::Error;
+use crate::hash::Digest;
+use crate::path::{Path, PathJoin};
+use crate::range::{make_range, verify_range_path};
+use crate::{find_bitmap_path, hash_bitmap};
+
+pub const BRT_HASH_SIZE: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrtErrorCode {
+    Success = 0,
+    Io = 1,
+    Unexpected = 2,
+    InvalidArgument = 3,
+    BufferTooSmall = 4,
+    NotFound = 5,
+    InvalidProof = 6,
+    Cancelled = 7,
+}
+
+impl From<Error> for BrtErrorCode {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(_) => BrtErrorCode::Io,
+            Error::InvalidInput(_) => BrtErrorCode::InvalidArgument,
+            Error::Unexpected => BrtErrorCode::Unexpected,
+            Error::Cancelled => BrtErrorCode::Cancelled,
+        }
+    }
+}
+
+/// Opaque handle to a non-revocation proof
+pub struct BrtProof {
+    range: (u32, u32),
+    path: Path<Vec<u8>>,
+    root: Vec<u8>,
+}
+
+impl BrtProof {
+    fn encoded_len(&self) -> usize {
+        12 + self.path.join.len() * (1 + BRT_HASH_SIZE)
+    }
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(&self.range.0.to_be_bytes());
+        buf[4..8].copy_from_slice(&self.range.1.to_be_bytes());
+        buf[8..12].copy_from_slice(&(self.path.join.len() as u32).to_be_bytes());
+        for (part, out) in self
+            .path
+            .join
+            .iter()
+            .zip(buf[12..].chunks_exact_mut(1 + BRT_HASH_SIZE))
+        {
+            let (dir, hash) = match part {
+                PathJoin::Left(h) => (0, h),
+                PathJoin::Right(h) => (1, h),
+            };
+            out[0] = dir;
+            out[1..].copy_from_slice(hash);
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < 12 {
+            return None;
+        }
+        let left = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+        let right = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+        let count = u32::from_be_bytes(buf[8..12].try_into().unwrap()) as usize;
+        let parts = &buf[12..];
+        if count.checked_mul(1 + BRT_HASH_SIZE) != Some(parts.len()) {
+            return None;
+        }
+        let mut join = Vec::with_capacity(count);
+        for part in parts.chunks_exact(1 + BRT_HASH_SIZE) {
+            let hash = part[1..].to_vec();
+            join.push(match part[0] {
+                0 => PathJoin::Left(hash),
+                1 => PathJoin::Right(hash),
+                _ => return None,
+            });
+        }
+        let leaf = Sha256::digest(&make_range(left, right)).to_vec();
+        Some(Self {
+            range: (left, right),
+            path: Path::new(leaf, join),
+            root: vec![],
+        })
+    }
+
+    fn verify(&self, index: u32, root: &[u8]) -> bool {
+        verify_range_path::<Sha256>(index, self.range, &self.path, root)
+    }
+}
+
+fn guard<F>(f: F) -> BrtErrorCode
+where
+    F: FnOnce() -> Result<(), BrtErrorCode> + UnwindSafe,
+{
+    match catch_unwind(f) {
+        Ok(Ok(())) => BrtErrorCode::Success,
+        Ok(Err(code)) => code,
+        Err(_) => BrtErrorCode::Unexpected,
+    }
+}
+
+unsafe fn input_slice<'a>(data: *const u8, len: usize) -> Result<&'a [u8], BrtErrorCode> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(BrtErrorCode::InvalidArgument)
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+/// Hash a bitmap buffer, writing the 32 byte root to `root_out`.
+///
+/// # Safety
+///
+/// `bitmap` must point to `bitmap_len` readable bytes and `root_out` to
+/// `BRT_HASH_SIZE` writable bytes. The count outputs may be null.
+#[no_mangle]
+pub unsafe extern "C" fn brt_hash_bitmap(
+    bitmap: *const u8,
+    bitmap_len: usize,
+    fill: bool,
+    root_out: *mut u8,
+    leaf_count_out: *mut usize,
+    filled_count_out: *mut usize,
+) -> BrtErrorCode {
+    guard(|| {
+        let bitmap = input_slice(bitmap, bitmap_len)?;
+        if root_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let result = hash_bitmap::<Sha256>(bitmap, fill)?;
+        let root = result.root.ok_or(BrtErrorCode::Unexpected)?;
+        slice::from_raw_parts_mut(root_out, BRT_HASH_SIZE).copy_from_slice(&root);
+        if !leaf_count_out.is_null() {
+            *leaf_count_out = result.leaf_count;
+        }
+        if !filled_count_out.is_null() {
+            *filled_count_out = result.filled_count;
+        }
+        Ok(())
+    })
+}
+
+/// Produce a proof that `index` is not revoked in a bitmap buffer.
+///
+/// Returns `NotFound` when the index is revoked or outside the registry,
+/// which holds the indices `1..=bitmap_len * 8`.
+///
+/// # Safety
+///
+/// `bitmap` must point to `bitmap_len` readable bytes and `proof_out` must be
+/// a valid pointer. The returned handle must be released with `brt_proof_free`.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_from_bitmap(
+    bitmap: *const u8,
+    bitmap_len: usize,
+    index: u32,
+    proof_out: *mut *mut BrtProof,
+) -> BrtErrorCode {
+    guard(|| {
+        let bitmap = input_slice(bitmap, bitmap_len)?;
+        if proof_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        if index == 0 || index as u64 > bitmap.len() as u64 * 8 {
+            return Err(BrtErrorCode::NotFound);
+        }
+        let (range, path, result) = find_bitmap_path::<Sha256>(bitmap, index)?;
+        match (range, path, result.root) {
+            (Some(range), Some(path), Some(root)) => {
+                let proof = BrtProof { range, path, root };
+                *proof_out = Box::into_raw(Box::new(proof));
+                Ok(())
+            }
+            _ => Err(BrtErrorCode::NotFound),
+        }
+    })
+}
+
+/// Load a proof previously written by `brt_proof_encode`.
+///
+/// # Safety
+///
+/// `data` must point to `data_len` readable bytes and `proof_out` must be
+/// a valid pointer. The returned handle must be released with `brt_proof_free`.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_decode(
+    data: *const u8,
+    data_len: usize,
+    proof_out: *mut *mut BrtProof,
+) -> BrtErrorCode {
+    guard(|| {
+        let data = input_slice(data, data_len)?;
+        if proof_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let proof = BrtProof::decode(data).ok_or(BrtErrorCode::InvalidProof)?;
+        *proof_out = Box::into_raw(Box::new(proof));
+        Ok(())
+    })
+}
+
+/// Write the encoded proof to `buf`, storing the required size in `written_out`.
+///
+/// Returns `BufferTooSmall` if `buf_len` is insufficient, in which case
+/// `written_out` still receives the required size.
+///
+/// # Safety
+///
+/// `proof` must be a live handle, `buf` must point to `buf_len` writable bytes
+/// and `written_out` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_encode(
+    proof: *const BrtProof,
+    buf: *mut u8,
+    buf_len: usize,
+    written_out: *mut usize,
+) -> BrtErrorCode {
+    guard(|| {
+        if proof.is_null() || written_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let proof = &*proof;
+        let size = proof.encoded_len();
+        *written_out = size;
+        if buf_len < size {
+            return Err(BrtErrorCode::BufferTooSmall);
+        }
+        if buf.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        proof.encode(slice::from_raw_parts_mut(buf, size));
+        Ok(())
+    })
+}
+
+/// Read the `(left, right)` range covered by a proof.
+///
+/// # Safety
+///
+/// `proof` must be a live handle and the outputs valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_range(
+    proof: *const BrtProof,
+    left_out: *mut u32,
+    right_out: *mut u32,
+) -> BrtErrorCode {
+    guard(|| {
+        if proof.is_null() || left_out.is_null() || right_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let (left, right) = (*proof).range;
+        *left_out = left;
+        *right_out = right;
+        Ok(())
+    })
+}
+
+/// Copy the 32 byte root of the tree a proof was produced from.
+///
+/// Returns `NotFound` for decoded proofs, which do not carry a root.
+///
+/// # Safety
+///
+/// `proof` must be a live handle and `root_out` must point to
+/// `BRT_HASH_SIZE` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_root(proof: *const BrtProof, root_out: *mut u8) -> BrtErrorCode {
+    guard(|| {
+        if proof.is_null() || root_out.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let root = &(*proof).root;
+        if root.is_empty() {
+            return Err(BrtErrorCode::NotFound);
+        }
+        slice::from_raw_parts_mut(root_out, BRT_HASH_SIZE).copy_from_slice(root);
+        Ok(())
+    })
+}
+
+/// Check that a proof shows `index` is not revoked in the tree with the given root.
+///
+/// Returns `Success` for a valid proof and `InvalidProof` otherwise.
+///
+/// # Safety
+///
+/// `proof` must be a live handle and `root` must point to `BRT_HASH_SIZE`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_verify(
+    proof: *const BrtProof,
+    index: u32,
+    root: *const u8,
+) -> BrtErrorCode {
+    guard(|| {
+        if proof.is_null() || root.is_null() {
+            return Err(BrtErrorCode::InvalidArgument);
+        }
+        let root = slice::from_raw_parts(root, BRT_HASH_SIZE);
+        if (*proof).verify(index, root) {
+            Ok(())
+        } else {
+            Err(BrtErrorCode::InvalidProof)
+        }
+    })
+}
+
+/// Release a proof handle.
+///
+/// # Safety
+///
+/// `proof` must be null or a handle not previously freed.
+#[no_mangle]
+pub unsafe extern "C" fn brt_proof_free(proof: *mut BrtProof) {
+    if !proof.is_null() {
+        drop(Box::from_raw(proof));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn test_header_current() {
+        // regenerate with `BRT_UPDATE_HEADER=1 cargo build --features ffi`
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/brangetree.h"));
+        let checked_in = include_str!("../include/brangetree.h");
+        assert_eq!(generated, checked_in, "include/brangetree.h is out of date");
+    }
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let mut bitmap = [0u8; 64];
+        bitmap[1] = 0x81;
+        bitmap[20] = 0xff;
+        let mut root = [0u8; BRT_HASH_SIZE];
+        let mut leaf_count = 0;
+        let code = unsafe {
+            brt_hash_bitmap(
+                bitmap.as_ptr(),
+                bitmap.len(),
+                true,
+                root.as_mut_ptr(),
+                &mut leaf_count,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(code, BrtErrorCode::Success);
+        assert_eq!(leaf_count, 4);
+
+        let mut proof = ptr::null_mut();
+        let code = unsafe { brt_proof_from_bitmap(bitmap.as_ptr(), bitmap.len(), 100, &mut proof) };
+        assert_eq!(code, BrtErrorCode::Success);
+        let mut proof_root = [0u8; BRT_HASH_SIZE];
+        unsafe {
+            assert_eq!(
+                brt_proof_root(proof, proof_root.as_mut_ptr()),
+                BrtErrorCode::Success
+            );
+        }
+        assert_eq!(proof_root, root);
+
+        let mut size = 0;
+        let code = unsafe { brt_proof_encode(proof, ptr::null_mut(), 0, &mut size) };
+        assert_eq!(code, BrtErrorCode::BufferTooSmall);
+        let mut buf = vec![0u8; size];
+        let code = unsafe { brt_proof_encode(proof, buf.as_mut_ptr(), buf.len(), &mut size) };
+        assert_eq!(code, BrtErrorCode::Success);
+        unsafe { brt_proof_free(proof) };
+
+        let mut decoded = ptr::null_mut();
+        let code = unsafe { brt_proof_decode(buf.as_ptr(), buf.len(), &mut decoded) };
+        assert_eq!(code, BrtErrorCode::Success);
+        let (mut left, mut right) = (0, 0);
+        unsafe {
+            brt_proof_range(decoded, &mut left, &mut right);
+            assert_eq!((left, right), (16, 161));
+            assert_eq!(
+                brt_proof_verify(decoded, 100, root.as_ptr()),
+                BrtErrorCode::Success
+            );
+            assert_eq!(
+                brt_proof_verify(decoded, 8, root.as_ptr()),
+                BrtErrorCode::InvalidProof
+            );
+            root[0] ^= 1;
+            assert_eq!(
+                brt_proof_verify(decoded, 100, root.as_ptr()),
+                BrtErrorCode::InvalidProof
+            );
+            brt_proof_free(decoded);
+        }
+
+        let code = unsafe { brt_proof_from_bitmap(bitmap.as_ptr(), bitmap.len(), 16, &mut proof) };
+        assert_eq!(code, BrtErrorCode::NotFound);
+    }
+
+    #[test]
+    fn test_proof_bounds() {
+        let bitmap = [0u8; 64];
+        for (index, expect) in &[
+            (0, BrtErrorCode::NotFound),
+            (1, BrtErrorCode::Success),
+            (512, BrtErrorCode::Success),
+            (513, BrtErrorCode::NotFound),
+            (u32::MAX - 1, BrtErrorCode::NotFound),
+        ] {
+            let mut proof = ptr::null_mut();
+            let code =
+                unsafe { brt_proof_from_bitmap(bitmap.as_ptr(), bitmap.len(), *index, &mut proof) };
+            assert_eq!(code, *expect, "{}", index);
+            if code == BrtErrorCode::Success {
+                unsafe { brt_proof_free(proof) };
+            }
+        }
+
+        // a path length not matching the data is rejected
+        let mut data = [0u8; 12 + 33];
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut decoded = ptr::null_mut();
+        let code = unsafe { brt_proof_decode(data.as_ptr(), data.len(), &mut decoded) };
+        assert_eq!(code, BrtErrorCode::InvalidProof);
+    }
+}
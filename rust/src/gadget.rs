@@ -0,0 +1,255 @@
+use bellperson::gadgets::boolean::{AllocatedBit, Boolean};
+use bellperson::gadgets::num::AllocatedNum;
+use bellperson::{Circuit, ConstraintSystem, LinearCombination, SynthesisError};
+use ff::Field;
+use generic_array::typenum::U2;
+use neptune::circuit::poseidon_hash;
+use neptune::poseidon::PoseidonConstants;
+use neptune::scalar_from_u64;
+use paired::bls12_381::{Bls12, Fr};
+
+use crate::path::{Path, PathJoin};
+
+/// Allocate a number constrained to 32 bits.
+pub fn alloc_u32<CS>(mut cs: CS, value: Option<u32>) -> Result<AllocatedNum<Bls12>, SynthesisError>
+where
+    CS: ConstraintSystem<Bls12>,
+{
+    let num = AllocatedNum::alloc(cs.namespace(|| "num"), || {
+        value
+            .map(|v| scalar_from_u64(v as u64))
+            .ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    let mut lc = LinearCombination::zero();
+    let mut coeff = Fr::one();
+    for i in 0..32 {
+        let bit = AllocatedBit::alloc(
+            cs.namespace(|| format!("bit {}", i)),
+            value.map(|v| v >> i & 1 == 1),
+        )?;
+        lc = lc + (coeff, bit.get_variable());
+        coeff.double();
+    }
+    cs.enforce(
+        || "bit decomposition",
+        |_| lc,
+        |lc| lc + CS::one(),
+        |lc| lc + num.get_variable(),
+    );
+    Ok(num)
+}
+
+/// Constrain `a < b` for numbers already known to fit in 32 bits.
+pub fn enforce_less_than<CS>(
+    mut cs: CS,
+    a: &AllocatedNum<Bls12>,
+    a_value: Option<u32>,
+    b: &AllocatedNum<Bls12>,
+    b_value: Option<u32>,
+) -> Result<(), SynthesisError>
+where
+    CS: ConstraintSystem<Bls12>,
+{
+    let diff_value = match (a_value, b_value) {
+        (Some(a), Some(b)) => Some(b.wrapping_sub(a).wrapping_sub(1)),
+        _ => None,
+    };
+    let diff = alloc_u32(cs.namespace(|| "difference"), diff_value)?;
+    // b - a - 1 = diff, so diff + a + 1 = b
+    cs.enforce(
+        || "less than",
+        |lc| lc + diff.get_variable() + a.get_variable() + CS::one(),
+        |lc| lc + CS::one(),
+        |lc| lc + b.get_variable(),
+    );
+    Ok(())
+}
+
+/// Constrain `left < index < right` and recompute the Poseidon root of a
+/// binary range tree from the leaf `left * 2^32 + right` and its path.
+///
+/// Each path entry holds the sibling and whether the sibling is on the left.
+pub fn range_path_gadget<CS>(
+    mut cs: CS,
+    constants: &PoseidonConstants<Bls12, U2>,
+    index: Option<u32>,
+    range: Option<(u32, u32)>,
+    path: &[Option<(Fr, bool)>],
+) -> Result<AllocatedNum<Bls12>, SynthesisError>
+where
+    CS: ConstraintSystem<Bls12>,
+{
+    let left_value = range.map(|r| r.0);
+    let right_value = range.map(|r| r.1);
+    let index_num = alloc_u32(cs.namespace(|| "index"), index)?;
+    let left = alloc_u32(cs.namespace(|| "left"), left_value)?;
+    let right = alloc_u32(cs.namespace(|| "right"), right_value)?;
+    enforce_less_than(
+        cs.namespace(|| "left < index"),
+        &left,
+        left_value,
+        &index_num,
+        index,
+    )?;
+    enforce_less_than(
+        cs.namespace(|| "index < right"),
+        &index_num,
+        index,
+        &right,
+        right_value,
+    )?;
+
+    let shift = scalar_from_u64::<Fr>(1 << 32);
+    let mut cur = AllocatedNum::alloc(cs.namespace(|| "leaf"), || {
+        let (l, r) = range.ok_or(SynthesisError::AssignmentMissing)?;
+        Ok(scalar_from_u64(((l as u64) << 32) | r as u64))
+    })?;
+    cs.enforce(
+        || "leaf packing",
+        |lc| lc + (shift, left.get_variable()) + right.get_variable(),
+        |lc| lc + CS::one(),
+        |lc| lc + cur.get_variable(),
+    );
+
+    for (level, part) in path.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("level {}", level));
+        let sibling = AllocatedNum::alloc(cs.namespace(|| "sibling"), || {
+            part.map(|p| p.0).ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        let is_left = Boolean::from(AllocatedBit::alloc(
+            cs.namespace(|| "sibling is left"),
+            part.map(|p| p.1),
+        )?);
+        let (l, r) = AllocatedNum::conditionally_reverse(
+            cs.namespace(|| "order"),
+            &cur,
+            &sibling,
+            &is_left,
+        )?;
+        cur = poseidon_hash(cs.namespace(|| "hash"), vec![l, r], constants)?;
+    }
+    Ok(cur)
+}
+
+/// A non-revocation circuit with the credential index, range leaf and path
+/// as private inputs and the tree root as the only public input.
+#[derive(Clone)]
+pub struct NonRevocationCircuit<'a> {
+    pub constants: &'a PoseidonConstants<Bls12, U2>,
+    pub index: Option<u32>,
+    pub range: Option<(u32, u32)>,
+    pub path: Vec<Option<(Fr, bool)>>,
+}
+
+impl<'a> NonRevocationCircuit<'a> {
+    pub fn new(
+        constants: &'a PoseidonConstants<Bls12, U2>,
+        index: u32,
+        range: (u32, u32),
+        path: &Path<Fr>,
+    ) -> Self {
+        let path = path
+            .join
+            .iter()
+            .map(|part| match part {
+                PathJoin::Left(h) => Some((*h, true)),
+                PathJoin::Right(h) => Some((*h, false)),
+            })
+            .collect();
+        Self {
+            constants,
+            index: Some(index),
+            range: Some(range),
+            path,
+        }
+    }
+
+    /// A circuit without assignments, for parameter generation.
+    pub fn blank(constants: &'a PoseidonConstants<Bls12, U2>, depth: usize) -> Self {
+        Self {
+            constants,
+            index: None,
+            range: None,
+            path: vec![None; depth],
+        }
+    }
+}
+
+impl Circuit<Bls12> for NonRevocationCircuit<'_> {
+    fn synthesize<CS: ConstraintSystem<Bls12>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let root = range_path_gadget(
+            cs.namespace(|| "range path"),
+            self.constants,
+            self.index,
+            self.range,
+            &self.path,
+        )?;
+        root.inputize(cs.namespace(|| "root"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::process_bitmap;
+    use crate::poseidon::PoseidonFold;
+    use crate::range::{RangeParser, RangePathTracker};
+    use bellperson::gadgets::test::TestConstraintSystem;
+    use neptune::bench_cs::BenchCS;
+
+    #[test]
+    fn test_gadget() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let mut bitmap = [0u8; 8];
+        bitmap[0] = 0x90;
+        bitmap[2] = 0x0f;
+        bitmap[5] = 0x01;
+        for index in 1..=64 {
+            let tracker = RangePathTracker::new(PoseidonFold::new(&constants), index);
+            let mut tracker = process_bitmap(&bitmap, RangeParser::new(tracker)).unwrap();
+            tracker.fill();
+            let (range, path, root) = tracker.result();
+            let root = root.unwrap();
+            let (range, path) = match (range, path) {
+                (Some(range), Some(path)) => (range, path),
+                _ => continue,
+            };
+
+            let mut cs = TestConstraintSystem::<Bls12>::new();
+            let circuit = NonRevocationCircuit::new(&constants, index, range, &path);
+            circuit.synthesize(&mut cs).unwrap();
+            assert!(cs.is_satisfied(), "{:?}", cs.which_is_unsatisfied());
+            assert!(cs.verify(&[root]));
+
+            // the same path cannot be used for an index outside the range
+            for bad_index in &[range.0, range.1] {
+                let mut cs = TestConstraintSystem::<Bls12>::new();
+                let circuit = NonRevocationCircuit::new(&constants, *bad_index, range, &path);
+                circuit.synthesize(&mut cs).unwrap();
+                assert!(!cs.is_satisfied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_blank() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let bitmap = [0x90u8];
+        let tracker = RangePathTracker::new(PoseidonFold::new(&constants), 3);
+        let mut tracker = process_bitmap(&bitmap, RangeParser::new(tracker)).unwrap();
+        tracker.fill();
+        let (range, path, _) = tracker.result();
+        let path = path.unwrap();
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        NonRevocationCircuit::new(&constants, 3, range.unwrap(), &path)
+            .synthesize(&mut cs)
+            .unwrap();
+        let mut bench = BenchCS::<Bls12>::new();
+        NonRevocationCircuit::blank(&constants, path.join.len())
+            .synthesize(&mut bench)
+            .unwrap();
+        assert_eq!(bench.num_constraints(), cs.num_constraints());
+        assert_eq!(bench.num_inputs(), cs.num_inputs());
+    }
+}
@@ -0,0 +1,204 @@
+//! Reproducible generation of test registries.
+//!
+//! A port of `scripts/gen-data.py`, producing the same gzip bitmap format
+//! from a seeded ChaCha generator, with a choice of revocation patterns.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::seq::index::sample;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::error::Error;
+use crate::registry::RevocationRegistry;
+
+/// The pattern of revoked indices in a generated registry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distribution {
+    /// Revoked indices chosen uniformly at random.
+    Uniform,
+    /// Bursts of consecutive revocations with random starting points and
+    /// lengths averaging `mean_burst`.
+    Clustered { mean_burst: u32 },
+    /// The lowest indices revoked, as when credentials expire in order.
+    Prefix,
+    /// Every second index revoked, producing the most leaves for the rate.
+    Alternating,
+}
+
+impl Distribution {
+    pub const DEFAULT_BURST: u32 = 32;
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Distribution::Uniform => f.write_str("uniform"),
+            Distribution::Clustered { mean_burst } => write!(f, "clustered:{}", mean_burst),
+            Distribution::Prefix => f.write_str("prefix"),
+            Distribution::Alternating => f.write_str("alternating"),
+        }
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let param = parts.next();
+        let invalid = || Error::InvalidInput(format!("unknown distribution: {}", s));
+        match (name, param) {
+            ("uniform", None) => Ok(Distribution::Uniform),
+            ("clustered", None) => Ok(Distribution::Clustered {
+                mean_burst: Self::DEFAULT_BURST,
+            }),
+            ("clustered", Some(burst)) => match burst.parse() {
+                Ok(mean_burst) if mean_burst > 0 => Ok(Distribution::Clustered { mean_burst }),
+                _ => Err(invalid()),
+            },
+            ("prefix", None) => Ok(Distribution::Prefix),
+            ("alternating", None) => Ok(Distribution::Alternating),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Generate a registry of `size` indices with the fraction `rate` of them
+/// revoked, such that the same seed always produces the same registry.
+pub fn generate_registry(
+    size: u32,
+    rate: f64,
+    seed: u64,
+    distribution: Distribution,
+) -> Result<RevocationRegistry, Error> {
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(Error::InvalidInput(format!("invalid rate: {}", rate)));
+    }
+    if let Distribution::Clustered { mean_burst: 0 } = distribution {
+        return Err(Error::InvalidInput("invalid mean burst: 0".into()));
+    }
+    let mut registry = RevocationRegistry::new(size);
+    let size = registry.capacity();
+    let count = (rate * size as f64).round() as u32;
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    match distribution {
+        Distribution::Uniform => {
+            for bit in sample(&mut rng, size as usize, count as usize) {
+                registry.revoke(bit as u32 + 1)?;
+            }
+        }
+        Distribution::Clustered { mean_burst } => {
+            let mut remain = count;
+            while remain > 0 {
+                let mut index = rng.gen_range(1..=size);
+                let mut burst = rng
+                    .gen_range(1..=mean_burst.saturating_mul(2) - 1)
+                    .min(remain);
+                while burst > 0 {
+                    if registry.revoke(index)? {
+                        burst -= 1;
+                        remain -= 1;
+                    }
+                    index = if index == size { 1 } else { index + 1 };
+                }
+            }
+        }
+        Distribution::Prefix => {
+            for index in 1..=count {
+                registry.revoke(index)?;
+            }
+        }
+        Distribution::Alternating => {
+            let evens = (2..=size).step_by(2);
+            let odds = (1..=size).step_by(2);
+            for index in evens.chain(odds).take(count as usize) {
+                registry.revoke(index)?;
+            }
+        }
+    }
+    Ok(registry)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for dist in &[
+            Distribution::Uniform,
+            Distribution::Clustered { mean_burst: 8 },
+            Distribution::Prefix,
+            Distribution::Alternating,
+        ] {
+            assert_eq!(dist.to_string().parse::<Distribution>().unwrap(), *dist);
+        }
+        assert_eq!(
+            "clustered".parse::<Distribution>().unwrap(),
+            Distribution::Clustered {
+                mean_burst: Distribution::DEFAULT_BURST
+            }
+        );
+        assert!("clustered:0".parse::<Distribution>().is_err());
+        assert!("random".parse::<Distribution>().is_err());
+    }
+
+    #[test]
+    fn test_counts() {
+        for dist in &[
+            Distribution::Uniform,
+            Distribution::Clustered { mean_burst: 16 },
+            Distribution::Prefix,
+            Distribution::Alternating,
+        ] {
+            for rate in &[0.0, 0.01, 0.25, 0.5, 0.9, 1.0] {
+                let registry = generate_registry(4096, *rate, 7, *dist).unwrap();
+                assert_eq!(
+                    registry.revoked_count(),
+                    (rate * 4096.0).round() as usize,
+                    "{} {}",
+                    dist,
+                    rate
+                );
+            }
+        }
+        assert!(generate_registry(16, 1.5, 0, Distribution::Uniform).is_err());
+        assert!(matches!(
+            generate_registry(16, 0.5, 0, Distribution::Clustered { mean_burst: 0 }),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let dist = Distribution::Clustered { mean_burst: 8 };
+        let a = generate_registry(10_000, 0.05, 42, dist).unwrap();
+        let b = generate_registry(10_000, 0.05, 42, dist).unwrap();
+        let c = generate_registry(10_000, 0.05, 43, dist).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_patterns() {
+        let prefix = generate_registry(64, 0.25, 0, Distribution::Prefix).unwrap();
+        assert_eq!(
+            prefix.ranges().collect::<Vec<_>>(),
+            vec![(0, 1), (16, u32::MAX)]
+        );
+
+        let alternating = generate_registry(64, 0.5, 0, Distribution::Alternating).unwrap();
+        assert_eq!(alternating.as_bitmap(), &[0x55; 8]);
+        assert_eq!(alternating.ranges().count(), 33);
+
+        // bursts produce far fewer ranges than uniform revocation
+        let clustered =
+            generate_registry(1 << 16, 0.1, 1, Distribution::Clustered { mean_burst: 32 }).unwrap();
+        let uniform = generate_registry(1 << 16, 0.1, 1, Distribution::Uniform).unwrap();
+        assert!(clustered.ranges().count() * 4 < uniform.ranges().count());
+    }
+}
@@ -1,4 +1,5 @@
-use std::marker::PhantomData;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
 
 pub use digest::Digest;
 
@@ -14,10 +15,16 @@ impl<H: Digest, B: AsRef<[u8]>> HashFold<H, B> {
     }
 }
 
+impl<H: Digest, B: AsRef<[u8]>> Default for HashFold<H, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<H: Digest, B: AsRef<[u8]>> TreeFold for HashFold<H, B> {
     type Leaf = B;
     type Target = Vec<u8>;
-    type Error = std::convert::Infallible;
+    type Error = core::convert::Infallible;
 
     fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
         Ok(H::digest(leaf.as_ref()).to_vec())
@@ -31,6 +38,45 @@ impl<H: Digest, B: AsRef<[u8]>> TreeFold for HashFold<H, B> {
     }
 }
 
+/// Hash leaves and interior nodes with the domain separation of RFC 6962,
+/// prefixing leaf data with `0x00` and pairs of nodes with `0x01`.
+pub struct Rfc6962Fold<H: Digest, B: AsRef<[u8]>> {
+    _pd: PhantomData<(H, B)>,
+}
+
+impl<H: Digest, B: AsRef<[u8]>> Rfc6962Fold<H, B> {
+    pub fn new() -> Self {
+        Self { _pd: PhantomData }
+    }
+}
+
+impl<H: Digest, B: AsRef<[u8]>> Default for Rfc6962Fold<H, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: Digest, B: AsRef<[u8]>> TreeFold for Rfc6962Fold<H, B> {
+    type Leaf = B;
+    type Target = Vec<u8>;
+    type Error = core::convert::Infallible;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let mut h = H::new();
+        h.input([0u8]);
+        h.input(leaf.as_ref());
+        Ok(h.result().to_vec())
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        let mut h = H::new();
+        h.input([1u8]);
+        h.input(a);
+        h.input(b);
+        Ok(h.result().to_vec())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -50,4 +96,27 @@ mod test {
         let root = hasher.result().to_vec();
         assert_eq!(result.unwrap(), root);
     }
+
+    #[test]
+    fn test_rfc6962() {
+        // the empty leaf and the root of the first two test leaves used by
+        // Certificate Transparency implementations
+        let leaves: Vec<Vec<u8>> = vec![vec![], vec![0]];
+        let (result, _) = TreeFolder::fold(
+            Rfc6962Fold::<Sha256, Vec<u8>>::new(),
+            leaves[..1].to_vec(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(result.unwrap()),
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d"
+        );
+        let (result, _) =
+            TreeFolder::fold(Rfc6962Fold::<Sha256, Vec<u8>>::new(), leaves, None).unwrap();
+        assert_eq!(
+            hex::encode(result.unwrap()),
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"
+        );
+    }
 }
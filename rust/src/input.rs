@@ -1,54 +1,165 @@
+use alloc::format;
+use core::convert::TryInto;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::prelude::*;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
 
+#[cfg(feature = "std")]
 use flate2::read::GzDecoder;
 
-use byte_slice_cast::*;
-
 use crate::error::Error;
 
 // could change to u32 depending on platform
 pub type BitBlock = u64;
 
+#[cfg(feature = "std")]
 pub struct ReadIter<R: Read> {
     buf: Vec<u8>,
     source: R,
+    cancel: Option<CancelToken>,
 }
 
+#[cfg(feature = "std")]
 impl<'a, R: Read> ReadIter<R> {
     pub fn new(source: R, bufsize: usize) -> Self {
         Self {
             buf: vec![0u8; bufsize],
             source,
+            cancel: None,
         }
     }
 
+    /// Stop with `Error::Cancelled` before the next read once the token is
+    /// cancelled.
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
     fn try_fold<B, F, E>(&mut self, init: B, mut f: F) -> Result<B, E>
     where
-        F: FnMut(B, &[u8]) -> Result<B, E>,
-        E: From<std::io::Error>,
+        F: FnMut(B, &[u8], &R) -> Result<B, E>,
+        E: From<Error>,
     {
         let mut acc = init;
         loop {
+            if matches!(&self.cancel, Some(token) if token.is_cancelled()) {
+                return Err(Error::Cancelled.into());
+            }
             match self.source.read(self.buf.as_mut_slice()) {
                 Ok(0) => return Ok(acc),
                 Ok(sz) => {
-                    acc = f(acc, &self.buf[0..sz])?;
+                    acc = f(acc, &self.buf[0..sz], &self.source)?;
                 }
-                Err(e) => return Err(e.into()),
+                Err(e) => return Err(Error::from(e).into()),
             }
         }
     }
 }
 
-pub fn fold_zipped_blocks<B, F, E>(path: String, init: B, f: F) -> Result<B, E>
+/// A flag shared with a running job, which stops with `Error::Cancelled`
+/// once it is set.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+#[cfg(feature = "std")]
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "std")]
+struct CountRead<R: Read> {
+    source: R,
+    count: u64,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for CountRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let sz = self.source.read(buf)?;
+        self.count += sz as u64;
+        Ok(sz)
+    }
+}
+
+/// Fold the blocks of a gzip bitmap along with the number of compressed
+/// bytes read so far, checking for cancellation before each block.
+#[cfg(feature = "std")]
+pub fn fold_zipped_blocks<R, B, F, E>(
+    source: R,
+    init: B,
+    cancel: Option<&CancelToken>,
+    mut f: F,
+) -> Result<B, E>
 where
-    F: FnMut(B, &[u8]) -> Result<B, E>,
-    E: From<std::io::Error>,
+    R: Read,
+    F: FnMut(B, &[u8], u64) -> Result<B, E>,
+    E: From<Error>,
 {
-    let fp = File::open(path)?;
-    let mut reader = ReadIter::new(GzDecoder::new(fp), 1024);
-    reader.try_fold(init, f)
+    let mut reader = ReadIter::new(GzDecoder::new(CountRead { source, count: 0 }), 1024);
+    if let Some(token) = cancel {
+        reader = reader.with_cancel(token.clone());
+    }
+    reader.try_fold(init, |acc, block, decoder| {
+        f(acc, block, decoder.get_ref().count)
+    })
+}
+
+/// The progress of a job reading a gzip bitmap.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Compressed bytes read from the source.
+    pub bytes_read: u64,
+    /// Bits passed to the `BitSink`.
+    pub bits: u64,
+    /// Leaves produced, if reported by the `BitSink`.
+    pub leaves: Option<usize>,
+}
+
+#[cfg(feature = "std")]
+type ProgressFn<'a> = Box<dyn FnMut(&Progress) + 'a>;
+
+/// A progress callback, called after each block, and a cancellation token
+/// for a job reading a gzip bitmap.
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub struct Monitor<'a> {
+    progress: Option<ProgressFn<'a>>,
+    cancel: Option<CancelToken>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Monitor<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_progress<F: FnMut(&Progress) + 'a>(mut self, f: F) -> Self {
+        self.progress = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
 }
 
 pub trait BitSink {
@@ -57,47 +168,255 @@ pub trait BitSink {
     fn process_bits(&mut self, revoked: bool, count: u32) -> Result<(), Error>;
 
     fn complete(self) -> Result<Self::Result, Error>;
+
+    /// The number of leaves produced so far, if known.
+    fn leaf_count(&self) -> Option<usize> {
+        None
+    }
 }
 
-pub fn process_zipped_bits<T>(path: String, proc: T) -> Result<T::Result, Error>
+/// Feed a `BitSink` from part of a bitmap, without completing it.
+pub fn process_block<T>(proc: &mut T, block: &[u8]) -> Result<(), Error>
 where
     T: BitSink,
 {
-    let bits = (std::mem::size_of::<BitBlock>() * 8) as u32;
-
-    let target = fold_zipped_blocks(path, proc, |mut proc, block| {
-        let mut size = block.len();
-        let remain_size = size % 8;
-        size -= remain_size;
-
-        let elts = block[..size].as_slice_of::<BitBlock>().unwrap();
-        for elt in elts {
-            let elt = if cfg!(target_endian = "little") {
-                elt.swap_bytes()
-            } else {
-                *elt
-            };
-
-            if elt == 0 || elt == u64::MAX {
-                proc.process_bits(elt != 0, bits)?;
-                continue;
-            }
+    const BLOCK_SIZE: usize = core::mem::size_of::<BitBlock>();
+    let bits = (BLOCK_SIZE * 8) as u32;
 
-            for idx in (0..bits).rev() {
-                proc.process_bits(elt >> idx & 1 != 0, 1)?;
-            }
+    let elts = block.chunks_exact(BLOCK_SIZE);
+    let remain = elts.remainder();
+    for elt in elts {
+        let elt = BitBlock::from_be_bytes(elt.try_into().unwrap());
+
+        if elt == 0 || elt == BitBlock::MAX {
+            proc.process_bits(elt != 0, bits)?;
+            continue;
         }
-        if remain_size > 0 {
-            for elt in &block[size..] {
-                for idx in (0..8).rev() {
-                    proc.process_bits(elt >> idx & 1 != 0, 1)?;
-                }
-            }
+
+        for idx in (0..bits).rev() {
+            proc.process_bits(elt >> idx & 1 != 0, 1)?;
+        }
+    }
+    for elt in remain {
+        for idx in (0..8).rev() {
+            proc.process_bits(elt >> idx & 1 != 0, 1)?;
         }
+    }
+    Ok(())
+}
 
-        Result::<_, Error>::Ok(proc)
-    })?;
+/// Feed a `BitSink` from strictly increasing registry indices of revoked
+/// entries, without materialising a bitmap.
+pub fn process_revoked_indices<I, T>(indices: I, mut proc: T) -> Result<T::Result, Error>
+where
+    I: IntoIterator<Item = u32>,
+    T: BitSink,
+{
+    let mut next = 1;
+    for index in indices {
+        if index < next || index == u32::MAX {
+            return Err(Error::InvalidInput(format!(
+                "unordered or reserved revoked index: {}",
+                index
+            )));
+        }
+        if index > next {
+            proc.process_bits(false, index - next)?;
+        }
+        proc.process_bits(true, 1)?;
+        next = index + 1;
+    }
+    proc.complete()
+}
+
+#[cfg(feature = "std")]
+pub fn process_zipped_bits<T>(path: String, proc: T) -> Result<T::Result, Error>
+where
+    T: BitSink,
+{
+    let fp = File::open(path)?;
+    process_zipped_reader(fp, proc)
+}
+
+#[cfg(feature = "std")]
+pub fn process_zipped_reader<R, T>(source: R, proc: T) -> Result<T::Result, Error>
+where
+    R: Read,
+    T: BitSink,
+{
+    process_zipped_reader_from(source, 0, proc)
+}
+
+/// Process a gzip bitmap after skipping the first `skip` bytes of the
+/// uncompressed bitmap, as when resuming from a checkpoint.
+#[cfg(feature = "std")]
+pub fn process_zipped_reader_from<R, T>(source: R, skip: u64, proc: T) -> Result<T::Result, Error>
+where
+    R: Read,
+    T: BitSink,
+{
+    process_zipped_reader_with(source, skip, proc, &mut Monitor::new())
+}
+
+/// Process a gzip bitmap as `process_zipped_reader_from`, reporting
+/// progress and checking for cancellation between blocks.
+#[cfg(feature = "std")]
+pub fn process_zipped_reader_with<R, T>(
+    source: R,
+    skip: u64,
+    proc: T,
+    monitor: &mut Monitor,
+) -> Result<T::Result, Error>
+where
+    R: Read,
+    T: BitSink,
+{
+    let mut bits = 0;
+    let Monitor { progress, cancel } = monitor;
+    let (target, _) = fold_zipped_blocks(
+        source,
+        (proc, skip),
+        cancel.as_ref(),
+        |(mut proc, skip), block, bytes_read| {
+            let offset = skip.min(block.len() as u64) as usize;
+            process_block(&mut proc, &block[offset..])?;
+            bits += (block.len() - offset) as u64 * 8;
+            if let Some(progress) = progress.as_mut() {
+                progress(&Progress {
+                    bytes_read,
+                    bits,
+                    leaves: proc.leaf_count(),
+                });
+            }
+            Result::<_, Error>::Ok((proc, skip - offset as u64))
+        },
+    )?;
 
     let result = target.complete()?;
     Ok(result)
 }
+
+pub fn process_bitmap<T>(bitmap: &[u8], mut proc: T) -> Result<T::Result, Error>
+where
+    T: BitSink,
+{
+    process_block(&mut proc, bitmap)?;
+    proc.complete()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    struct BitCollect {
+        result: Vec<bool>,
+    }
+
+    impl BitSink for BitCollect {
+        type Result = Vec<bool>;
+
+        fn process_bits(&mut self, revoked: bool, count: u32) -> Result<(), Error> {
+            self.result
+                .resize(self.result.len() + count as usize, revoked);
+            Ok(())
+        }
+
+        fn complete(self) -> Result<Self::Result, Error> {
+            Ok(self.result)
+        }
+    }
+
+    #[test]
+    fn test_bit_order() {
+        let mut bitmap = vec![0u8; 9];
+        bitmap[0] = 0x80;
+        bitmap[7] = 0x01;
+        bitmap[8] = 0x40;
+        let bits = process_bitmap(&bitmap, BitCollect { result: vec![] }).unwrap();
+        assert_eq!(bits.len(), 72);
+        let set: Vec<usize> = (0..bits.len()).filter(|idx| bits[*idx]).collect();
+        assert_eq!(set, vec![0, 63, 65]);
+    }
+
+    #[test]
+    fn test_revoked_indices() {
+        let bits = process_revoked_indices(vec![1, 64, 66], BitCollect { result: vec![] }).unwrap();
+        assert_eq!(bits.len(), 66);
+        let set: Vec<usize> = (0..bits.len()).filter(|idx| bits[*idx]).collect();
+        assert_eq!(set, vec![0, 63, 65]);
+
+        for bad in &[vec![0], vec![3, 2], vec![2, 2], vec![u32::MAX]] {
+            assert!(matches!(
+                process_revoked_indices(bad.clone(), BitCollect { result: vec![] }),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn zipped_registry() -> Vec<u8> {
+        let writer = crate::output::BitmapWriter::new(vec![]).with_size(24576);
+        process_revoked_indices((1..3000).map(|idx| idx * 7), writer).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_progress() {
+        use crate::range::{range_hasher, RangeParser};
+        use sha2::Sha256;
+
+        let zipped = zipped_registry();
+        let mut reports: Vec<Progress> = vec![];
+        let mut monitor = Monitor::new().with_progress(|progress| reports.push(*progress));
+        let parser = RangeParser::new(range_hasher::<Sha256>());
+        let hasher =
+            process_zipped_reader_with(zipped.as_slice(), 0, parser, &mut monitor).unwrap();
+        drop(monitor);
+
+        assert!(reports.len() >= 3);
+        for pair in reports.windows(2) {
+            assert!(pair[0].bits < pair[1].bits);
+            assert!(pair[0].bytes_read <= pair[1].bytes_read);
+            assert!(pair[0].leaves <= pair[1].leaves);
+        }
+        let last = reports.last().unwrap();
+        assert_eq!(last.bits, 24576);
+        assert!(last.bytes_read > 0 && last.bytes_read <= zipped.len() as u64);
+        // the final leaf is added on completion
+        assert_eq!(last.leaves, Some(hasher.len() - 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_cancel() {
+        let zipped = zipped_registry();
+        let token = CancelToken::new();
+        let mut reports = 0;
+        let mut monitor = Monitor::new()
+            .with_cancel(token.clone())
+            .with_progress(|_| {
+                reports += 1;
+                token.cancel();
+            });
+        let result = process_zipped_reader_with(
+            zipped.as_slice(),
+            0,
+            BitCollect { result: vec![] },
+            &mut monitor,
+        );
+        drop(monitor);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(reports, 1);
+
+        let mut monitor = Monitor::new().with_cancel(token);
+        let result = process_zipped_reader_with(
+            zipped.as_slice(),
+            0,
+            BitCollect { result: vec![] },
+            &mut monitor,
+        );
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,228 @@
+use alloc::vec::Vec;
+
+use crate::error::Error;
+#[cfg(feature = "std")]
+use crate::input::process_zipped_bits;
+use crate::input::{process_bitmap, BitSink};
+use crate::range::{RangeParser, RangeTarget};
+
+/// Statistics about a registry bitmap.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryStats {
+    pub total_bits: u64,
+    pub revoked: u64,
+    /// The number of leaves (non-revoked ranges) before filling.
+    pub leaf_count: usize,
+    /// The number of leaves after filling to a power of two.
+    pub filled_count: usize,
+    /// The lengths of the longest and shortest runs of non-revoked bits
+    /// within the bitmap.
+    pub longest_run: u64,
+    pub shortest_run: Option<u64>,
+    /// The number of non-revoked runs with a length between `2^k` and
+    /// `2^(k+1) - 1`, for each `k`.
+    pub run_histogram: Vec<u64>,
+    /// The height of the tree before filling, which is the longest path.
+    pub depth: usize,
+    /// The height of the tree after filling, which is the length of every
+    /// path.
+    pub filled_depth: usize,
+}
+
+impl RegistryStats {
+    /// The size in bytes of the sibling hashes in a path through the filled
+    /// tree, for a given hash size.
+    pub fn proof_size(&self, hash_size: usize) -> usize {
+        self.filled_depth * hash_size
+    }
+
+    pub fn non_revoked(&self) -> u64 {
+        self.total_bits - self.revoked
+    }
+}
+
+/// The height of an unfilled tree, where the peaks left by the binary
+/// carry of `TreeFolder` are folded from the right.
+fn unfilled_depth(leaf_count: usize) -> usize {
+    let mut depth = None;
+    for height in 0..usize::BITS as usize {
+        if leaf_count >> height & 1 != 0 {
+            depth = Some(match depth {
+                None => height,
+                Some(d) => height.max(d) + 1,
+            });
+        }
+    }
+    depth.unwrap_or(0)
+}
+
+#[derive(Default)]
+struct RangeCount {
+    count: usize,
+}
+
+impl RangeTarget for RangeCount {
+    type Error = Error;
+
+    fn push_range(&mut self, _left: u32, _right: u32) -> Result<(), Self::Error> {
+        self.count += 1;
+        Ok(())
+    }
+
+    fn leaf_count(&self) -> Option<usize> {
+        Some(self.count)
+    }
+}
+
+/// Collect `RegistryStats` in a single pass over a bitmap.
+pub struct Inspector {
+    parser: RangeParser<RangeCount>,
+    stats: RegistryStats,
+    run: u64,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            parser: RangeParser::new(RangeCount::default()),
+            stats: RegistryStats::default(),
+            run: 0,
+        }
+    }
+
+    fn end_run(&mut self) {
+        if self.run == 0 {
+            return;
+        }
+        let run = self.run;
+        let stats = &mut self.stats;
+        stats.longest_run = stats.longest_run.max(run);
+        stats.shortest_run = Some(stats.shortest_run.map_or(run, |s| s.min(run)));
+        let bucket = (63 - run.leading_zeros()) as usize;
+        if stats.run_histogram.len() <= bucket {
+            stats.run_histogram.resize(bucket + 1, 0);
+        }
+        stats.run_histogram[bucket] += 1;
+        self.run = 0;
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitSink for Inspector {
+    type Result = RegistryStats;
+
+    fn process_bits(&mut self, revoked: bool, count: u32) -> Result<(), Error> {
+        self.stats.total_bits += count as u64;
+        if revoked {
+            self.stats.revoked += count as u64;
+            self.end_run();
+        } else {
+            self.run += count as u64;
+        }
+        self.parser.process_bits(revoked, count)
+    }
+
+    fn complete(mut self) -> Result<Self::Result, Error> {
+        self.end_run();
+        let leaf_count = self.parser.complete()?.count;
+        let mut stats = self.stats;
+        stats.leaf_count = leaf_count;
+        stats.filled_count = leaf_count.next_power_of_two();
+        stats.depth = unfilled_depth(leaf_count);
+        stats.filled_depth = stats.filled_count.trailing_zeros() as usize;
+        Ok(stats)
+    }
+
+    fn leaf_count(&self) -> Option<usize> {
+        self.parser.leaf_count()
+    }
+}
+
+pub fn inspect_bitmap(bitmap: &[u8]) -> Result<RegistryStats, Error> {
+    process_bitmap(bitmap, Inspector::new())
+}
+
+#[cfg(feature = "std")]
+pub fn inspect_zipped(path: String) -> Result<RegistryStats, Error> {
+    process_zipped_bits(path, Inspector::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::range_hasher;
+    use crate::tree::test::TestFold;
+    use crate::tree::TreeFolder;
+    use alloc::vec;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_unfilled_depth() {
+        for leaf_count in 1..40usize {
+            let leaves = (0..leaf_count).map(|n| n.to_string());
+            let (result, _) = TreeFolder::fold(TestFold {}, leaves, None).unwrap();
+            let result = result.unwrap();
+            let mut depth = 0;
+            let mut max_depth = 0;
+            for c in result.chars() {
+                match c {
+                    '[' => {
+                        depth += 1;
+                        max_depth = max_depth.max(depth);
+                    }
+                    ']' => depth -= 1,
+                    _ => (),
+                }
+            }
+            assert_eq!(unfilled_depth(leaf_count), max_depth, "{}", leaf_count);
+        }
+        assert_eq!(unfilled_depth(0), 0);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let mut bitmap = vec![0u8; 16];
+        bitmap[0] = 0xc0;
+        bitmap[1] = 0x01;
+        bitmap[8..16].copy_from_slice(&[0xff; 8]);
+        let stats = inspect_bitmap(&bitmap).unwrap();
+        assert_eq!(stats.total_bits, 128);
+        assert_eq!(stats.revoked, 67);
+        assert_eq!(stats.non_revoked(), 61);
+        // ranges (0, 1), (2, 16), (16, 65), (128, MAX)
+        assert_eq!(stats.leaf_count, 4);
+        assert_eq!(stats.filled_count, 4);
+        assert_eq!((stats.depth, stats.filled_depth), (2, 2));
+        assert_eq!(stats.longest_run, 48);
+        assert_eq!(stats.shortest_run, Some(13));
+        assert_eq!(stats.run_histogram, vec![0, 0, 0, 1, 0, 1]);
+        assert_eq!(stats.proof_size(32), 64);
+
+        let mut hasher =
+            process_bitmap(&bitmap, RangeParser::new(range_hasher::<Sha256>())).unwrap();
+        assert_eq!(hasher.len(), stats.leaf_count);
+        hasher.fill();
+        assert_eq!(hasher.len(), stats.filled_count);
+    }
+
+    #[test]
+    fn test_inspect_unfilled() {
+        // five ranges, as peaks of 4 and 1 leaves
+        let stats = inspect_bitmap(&[0x55]).unwrap();
+        assert_eq!(stats.leaf_count, 5);
+        assert_eq!(stats.filled_count, 8);
+        assert_eq!((stats.depth, stats.filled_depth), (3, 3));
+        assert_eq!(stats.shortest_run, Some(1));
+        assert_eq!(stats.run_histogram, vec![4]);
+
+        let empty = inspect_bitmap(&[]).unwrap();
+        assert_eq!(empty.leaf_count, 1);
+        assert_eq!((empty.depth, empty.filled_depth), (0, 0));
+        assert_eq!(empty.shortest_run, None);
+    }
+}
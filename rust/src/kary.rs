@@ -0,0 +1,565 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub trait KTreeFold {
+    type Leaf;
+    type Target: Clone;
+    type Error: core::fmt::Debug;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error>;
+
+    // receives between 2 and `arity` children, fewer only when folding the
+    // peaks of an unfilled tree
+    fn fold(&mut self, children: &[Self::Target]) -> Result<Self::Target, Self::Error>;
+
+    fn start_fill(&mut self) {}
+    fn end_fill(&mut self) {}
+}
+
+pub struct KTreeFolder<T: KTreeFold> {
+    base: T,
+    arity: usize,
+    stack: Vec<T::Target>,
+    depths: Vec<usize>,
+    leaf_count: usize,
+}
+
+impl<T: KTreeFold> KTreeFolder<T> {
+    pub fn new(base: T, arity: usize) -> Self {
+        assert!(arity >= 2, "arity must be at least 2");
+        Self {
+            base,
+            arity,
+            stack: vec![],
+            depths: vec![],
+            leaf_count: 0,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn fold<L>(
+        base: T,
+        arity: usize,
+        leaves: L,
+        fill_input: Option<T::Leaf>,
+    ) -> Result<(Option<T::Target>, T), T::Error>
+    where
+        L: IntoIterator<Item = T::Leaf>,
+    {
+        let mut inst = Self::new(base, arity);
+        inst.extend(leaves)?;
+        if let Some(fill_input) = fill_input {
+            inst.fill(fill_input)?;
+        }
+        inst.result()
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn push_node(&mut self, node: T::Target, depth: usize) -> Result<(), T::Error> {
+        self.stack.push(node);
+        self.depths.push(depth);
+        let k = self.arity;
+        loop {
+            let len = self.stack.len();
+            if len < k || self.depths[len - k] != self.depths[len - 1] {
+                break;
+            }
+            let depth = self.depths[len - 1];
+            let h = self.base.fold(&self.stack[len - k..])?;
+            self.stack.truncate(len - k);
+            self.depths.truncate(len - k);
+            self.stack.push(h);
+            self.depths.push(depth + 1);
+        }
+        Ok(())
+    }
+
+    pub fn push(&mut self, leaf: &T::Leaf) -> Result<(), T::Error> {
+        let h = self.base.input(leaf)?;
+        self.push_node(h, 0)?;
+        self.leaf_count += 1;
+        Ok(())
+    }
+
+    pub fn extend<L>(&mut self, leaves: L) -> Result<(), T::Error>
+    where
+        L: IntoIterator<Item = T::Leaf>,
+    {
+        for leaf in leaves.into_iter() {
+            self.push(&leaf)?;
+        }
+        Ok(())
+    }
+
+    pub fn fill(&mut self, fill_input: T::Leaf) -> Result<usize, T::Error> {
+        let mut fill_cache: Vec<T::Target> = vec![];
+        let k = self.arity;
+
+        let mut filler = |depth: usize, base: &mut T| -> Result<T::Target, T::Error> {
+            while fill_cache.len() <= depth {
+                let h = match fill_cache.last() {
+                    None => base.input(&fill_input)?,
+                    Some(prev) => base.fold(&vec![prev.clone(); k])?,
+                };
+                fill_cache.push(h);
+            }
+            Ok(fill_cache[depth].clone())
+        };
+
+        let mut height = 0;
+        let mut size = 1;
+        while size < self.leaf_count {
+            size *= k;
+            height += 1;
+        }
+
+        let mut fill_count = 0;
+        for depth in 0..height {
+            while self.depths.last() == Some(&depth) {
+                self.base.start_fill();
+                let h = filler(depth, &mut self.base)?;
+                self.base.end_fill();
+                self.push_node(h, depth)?;
+                fill_count += k.pow(depth as u32);
+            }
+        }
+        self.leaf_count += fill_count;
+        Ok(fill_count)
+    }
+
+    pub fn result(mut self) -> Result<(Option<T::Target>, T), T::Error> {
+        while self.stack.len() > 1 {
+            let len = self.stack.len();
+            let mut start = len - 1;
+            while start > 0 && self.depths[start - 1] == self.depths[len - 1] {
+                start -= 1;
+            }
+            if start == len - 1 {
+                // a single carried node joins the peaks of the next level
+                start -= 1;
+                while start > 0 && self.depths[start - 1] == self.depths[len - 2] {
+                    start -= 1;
+                }
+            }
+            let depth = self.depths[start];
+            let h = self.base.fold(&self.stack[start..])?;
+            self.stack.truncate(start);
+            self.depths.truncate(start);
+            self.stack.push(h);
+            self.depths.push(depth + 1);
+        }
+        Ok((self.stack.last().cloned(), self.base))
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+
+    pub fn update_base<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        f(&mut self.base)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KPathJoin<T> {
+    pub position: usize,
+    pub siblings: Vec<T>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct KPath<T: Clone> {
+    pub leaf: T,
+    pub join: Vec<KPathJoin<T>>,
+}
+
+impl<T: Clone> KPath<T> {
+    pub fn new(leaf: T, join: Vec<KPathJoin<T>>) -> Self {
+        Self { leaf, join }
+    }
+
+    pub fn len(&self) -> usize {
+        1 + self.join.len()
+    }
+
+    /// True for the path of a single-leaf tree, which has no joins.
+    pub fn is_empty(&self) -> bool {
+        self.join.is_empty()
+    }
+
+    pub fn join_at(&mut self, position: usize, siblings: Vec<T>) {
+        self.join.push(KPathJoin { position, siblings })
+    }
+
+    /// Fold the path into a root, or `None` if a join has more than `arity`
+    /// children or a position outside of them.
+    pub fn fold<F>(self, arity: usize, mut f: F) -> Option<T>
+    where
+        F: FnMut(Vec<T>) -> T,
+    {
+        let mut result = self.leaf;
+        for part in self.join {
+            if part.siblings.len() >= arity || part.position > part.siblings.len() {
+                return None;
+            }
+            let mut children = part.siblings;
+            children.insert(part.position, result);
+            result = f(children);
+        }
+        Some(result)
+    }
+
+    pub fn verify<F>(&self, arity: usize, root: &T, f: F) -> bool
+    where
+        T: PartialEq,
+        F: FnMut(Vec<T>) -> T,
+    {
+        self.clone().fold(arity, f).as_ref() == Some(root)
+    }
+}
+
+/// The position of a leaf and the number of children at each join of its
+/// path, following the shape produced by `KTreeFolder` for a filled or
+/// unfilled tree.
+pub fn kpath_positions(
+    index: usize,
+    leaf_count: usize,
+    arity: usize,
+) -> Option<Vec<(usize, usize)>> {
+    if arity < 2 || index >= leaf_count {
+        return None;
+    }
+    // the complete subtrees left on the stack, deepest first
+    let mut digits = vec![];
+    let mut count = leaf_count;
+    while count > 0 {
+        digits.push(count % arity);
+        count /= arity;
+    }
+    let mut joins = vec![];
+    let mut depths = vec![];
+    let mut tracked = None;
+    let mut offset = 0;
+    for (depth, count) in digits.iter().enumerate().rev() {
+        let size = arity.pow(depth as u32);
+        for _ in 0..*count {
+            if tracked.is_none() && index < offset + size {
+                let mut local = index - offset;
+                for _ in 0..depth {
+                    joins.push((local % arity, arity));
+                    local /= arity;
+                }
+                tracked.replace(depths.len());
+            }
+            depths.push(depth);
+            offset += size;
+        }
+    }
+    let mut tracked = tracked?;
+    // the peaks are joined as in `KTreeFolder::result`
+    while depths.len() > 1 {
+        let len = depths.len();
+        let mut start = len - 1;
+        while start > 0 && depths[start - 1] == depths[len - 1] {
+            start -= 1;
+        }
+        if start == len - 1 {
+            start -= 1;
+            while start > 0 && depths[start - 1] == depths[len - 2] {
+                start -= 1;
+            }
+        }
+        if tracked >= start {
+            joins.push((tracked - start, len - start));
+            tracked = start;
+        }
+        let depth = depths[start];
+        depths.truncate(start);
+        depths.push(depth + 1);
+    }
+    Some(joins)
+}
+
+/// A `KPath` along with the position of its leaf, the number of leaves in
+/// the tree, filled or unfilled, and the arity of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KLeafPath<T: Clone> {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub arity: usize,
+    pub path: KPath<T>,
+}
+
+impl<T: Clone> KLeafPath<T> {
+    pub fn new(index: usize, leaf_count: usize, arity: usize, path: KPath<T>) -> Self {
+        Self {
+            index,
+            leaf_count,
+            arity,
+            path,
+        }
+    }
+
+    /// Check that the join positions and sizes are those of the leaf
+    /// position.
+    pub fn check_positions(&self) -> bool {
+        match kpath_positions(self.index, self.leaf_count, self.arity) {
+            Some(joins) => {
+                joins.len() == self.path.join.len()
+                    && joins
+                        .iter()
+                        .zip(&self.path.join)
+                        .all(|((position, size), join)| {
+                            *position == join.position && *size == join.siblings.len() + 1
+                        })
+            }
+            None => false,
+        }
+    }
+
+    pub fn verify<F>(&self, root: &T, f: F) -> bool
+    where
+        T: PartialEq,
+        F: FnMut(Vec<T>) -> T,
+    {
+        self.check_positions() && self.path.verify(self.arity, root, f)
+    }
+}
+
+pub struct KPathTracker<T: KTreeFold> {
+    base: T,
+    input_index: usize,
+    stack_index: usize,
+    track_input_index: Option<usize>,
+    track_stack_index: Option<usize>,
+    path: Option<KPath<T::Target>>,
+    fill: bool,
+}
+
+impl<T: KTreeFold> KPathTracker<T> {
+    pub fn new(base: T, track_input_index: Option<usize>) -> Self {
+        Self {
+            base,
+            input_index: 0,
+            stack_index: 0,
+            track_input_index,
+            track_stack_index: None,
+            path: None,
+            fill: false,
+        }
+    }
+
+    pub fn path_result(&self) -> Option<KPath<T::Target>> {
+        self.path.clone()
+    }
+
+    /// The path with its leaf position, given the final number of leaves
+    /// and the arity of the tree.
+    pub fn leaf_path_result(
+        &self,
+        leaf_count: usize,
+        arity: usize,
+    ) -> Option<KLeafPath<T::Target>> {
+        let index = self.track_input_index?;
+        self.path
+            .clone()
+            .map(|path| KLeafPath::new(index, leaf_count, arity, path))
+    }
+
+    pub fn track_index(&mut self, index: usize) {
+        self.track_input_index.replace(index);
+        self.track_stack_index.take();
+        self.path.take();
+    }
+
+    pub fn track_next(&mut self) {
+        self.track_index(self.input_index)
+    }
+}
+
+impl<T: KTreeFold> KTreeFold for KPathTracker<T> {
+    type Leaf = T::Leaf;
+    type Target = T::Target;
+    type Error = T::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let r = self.base.input(leaf)?;
+        if !self.fill {
+            if self.track_input_index == Some(self.input_index) {
+                self.path.replace(KPath::new(r.clone(), vec![]));
+                self.track_stack_index.replace(self.stack_index);
+            }
+            self.input_index += 1;
+            self.stack_index += 1;
+        }
+        Ok(r)
+    }
+
+    fn fold(&mut self, children: &[Self::Target]) -> Result<Self::Target, Self::Error> {
+        let r = self.base.fold(children)?;
+        if !self.fill {
+            let start = self.stack_index - children.len();
+            if let Some(idx) = self.track_stack_index {
+                if idx >= start {
+                    let position = idx - start;
+                    let mut siblings = children.to_vec();
+                    siblings.remove(position);
+                    self.path.as_mut().unwrap().join_at(position, siblings);
+                    self.track_stack_index.replace(start);
+                }
+            }
+            self.stack_index = start + 1;
+        }
+        Ok(r)
+    }
+
+    fn start_fill(&mut self) {
+        self.fill = true;
+    }
+
+    fn end_fill(&mut self) {
+        self.fill = false;
+        self.stack_index += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    pub struct TestKFold;
+
+    impl KTreeFold for TestKFold {
+        type Leaf = String;
+        type Target = String;
+        type Error = String;
+
+        fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+            Ok(leaf.clone())
+        }
+
+        fn fold(&mut self, children: &[Self::Target]) -> Result<Self::Target, Self::Error> {
+            Ok(format!("[{}]", children.join(",")))
+        }
+    }
+
+    fn join(children: Vec<String>) -> String {
+        format!("[{}]", children.join(","))
+    }
+
+    #[test]
+    fn test_binary() {
+        let leaves = (0..=4).map(|n| n.to_string());
+        let (result, _) = KTreeFolder::fold(TestKFold {}, 2, leaves, None).unwrap();
+        assert_eq!(result.unwrap(), "[[[0,1],[2,3]],4]");
+    }
+
+    #[test]
+    fn test_unfilled() {
+        let leaves = (0..7).map(|n| n.to_string());
+        let (result, _) = KTreeFolder::fold(TestKFold {}, 3, leaves, None).unwrap();
+        assert_eq!(result.unwrap(), "[[0,1,2],[3,4,5],6]");
+
+        let leaves = (0..11).map(|n| n.to_string());
+        let (result, _) = KTreeFolder::fold(TestKFold {}, 3, leaves, None).unwrap();
+        assert_eq!(result.unwrap(), "[[[0,1,2],[3,4,5],[6,7,8]],[9,10]]");
+    }
+
+    #[test]
+    fn test_filled() {
+        let leaves = (0..9).map(|n| n.to_string());
+        let (result, _) =
+            KTreeFolder::fold(TestKFold {}, 3, leaves, Some("E".to_string())).unwrap();
+        assert_eq!(result.unwrap(), "[[0,1,2],[3,4,5],[6,7,8]]");
+
+        let leaves = (0..5).map(|n| n.to_string());
+        let mut folder = KTreeFolder::new(TestKFold {}, 4);
+        folder.extend(leaves).unwrap();
+        assert_eq!(folder.fill("E".to_string()).unwrap(), 11);
+        assert_eq!(folder.len(), 16);
+        let (result, _) = folder.result().unwrap();
+        assert_eq!(result.unwrap(), "[[0,1,2,3],[4,E,E,E],[E,E,E,E],[E,E,E,E]]");
+    }
+
+    #[test]
+    fn test_track() {
+        let leaves = (0..9).map(|n| n.to_string());
+        let tracker = KPathTracker::new(TestKFold {}, Some(4));
+        let (result, tracker) = KTreeFolder::fold(tracker, 3, leaves, None).unwrap();
+        let result = result.unwrap();
+        let path = tracker.path_result().unwrap();
+        assert_eq!(
+            path,
+            KPath::new(
+                "4".to_string(),
+                vec![
+                    KPathJoin {
+                        position: 1,
+                        siblings: vec!["3".to_string(), "5".to_string()]
+                    },
+                    KPathJoin {
+                        position: 1,
+                        siblings: vec!["[0,1,2]".to_string(), "[6,7,8]".to_string()]
+                    },
+                ]
+            )
+        );
+        assert!(path.verify(3, &result, join));
+
+        // a position past the children, or a join wider than the arity
+        let mut bad = path.clone();
+        bad.join[0].position = 3;
+        assert_eq!(bad.fold(3, join), None);
+        assert!(!path.verify(2, &result, join));
+
+        // a valid fold for the wrong leaf position
+        let leaf_path = tracker.leaf_path_result(9, 3).unwrap();
+        assert!(leaf_path.verify(&result, join));
+        let mut swapped = leaf_path.clone();
+        swapped.path.join[0] = KPathJoin {
+            position: 0,
+            siblings: vec!["3".to_string(), "5".to_string()],
+        };
+        assert!(!swapped.verify(&"[[0,1,2],[4,3,5],[6,7,8]]".to_string(), join));
+        assert!(swapped
+            .path
+            .verify(3, &"[[0,1,2],[4,3,5],[6,7,8]]".to_string(), join));
+    }
+
+    #[test]
+    fn test_track_multiple() {
+        for arity in 2..=5 {
+            for count in 1..40 {
+                let leaves: Vec<String> = (0..count).map(|n| n.to_string()).collect();
+                for fill in &[None, Some("E".to_string())] {
+                    for i in 0..count {
+                        let tracker = KPathTracker::new(TestKFold {}, Some(i));
+                        let (result, tracker) =
+                            KTreeFolder::fold(tracker, arity, leaves.clone(), fill.clone())
+                                .unwrap();
+                        let mut leaf_count = count;
+                        if fill.is_some() {
+                            leaf_count = 1;
+                            while leaf_count < count {
+                                leaf_count *= arity;
+                            }
+                        }
+                        let path = tracker.leaf_path_result(leaf_count, arity).unwrap();
+                        assert_eq!(path.path.leaf, i.to_string());
+                        assert!(path.verify(&result.unwrap(), join));
+                        assert_eq!(path.index, i);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! Non-membership trees over sorted keys of any type.
+//!
+//! Where a range tree is built over dense integer indices, a keyed tree is
+//! built over a sorted set of revoked keys, such as hashes of revoked
+//! identifiers. Each leaf holds a pair of adjacent keys, with `Begin` and
+//! `End` sentinels before the first key and after the last, so a key absent
+//! from the set falls strictly between the bounds of exactly one leaf.
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use crate::error::Error;
+use crate::hash::{Digest, HashFold};
+use crate::path::{Path, PathTracker};
+use crate::tree::{TreeFold, TreeFolder};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyBound<K> {
+    Begin,
+    Key(K),
+    End,
+}
+
+impl<K: AsRef<[u8]>> KeyBound<K> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            KeyBound::Begin => out.push(0),
+            KeyBound::Key(key) => {
+                let key = key.as_ref();
+                out.push(1);
+                out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+                out.extend_from_slice(key);
+            }
+            KeyBound::End => out.push(2),
+        }
+    }
+}
+
+/// Encode the leaf for a pair of adjacent bounds. Keys are length-prefixed
+/// and tagged, so that sentinels never collide with real keys.
+pub fn make_key_range<K: AsRef<[u8]>>(left: &KeyBound<K>, right: &KeyBound<K>) -> Vec<u8> {
+    let mut range = Vec::new();
+    left.encode_into(&mut range);
+    right.encode_into(&mut range);
+    range
+}
+
+pub fn key_hasher<H: Digest>() -> KeyTreeFolder<HashFold<H, Vec<u8>>> {
+    KeyTreeFolder::new(HashFold::<H, Vec<u8>>::new())
+}
+
+pub fn key_path_hasher<H: Digest, K: Ord>(find_key: K) -> KeyPathTracker<HashFold<H, Vec<u8>>, K> {
+    KeyPathTracker::new(HashFold::<H, Vec<u8>>::new(), find_key)
+}
+
+pub fn verify_key_path<H: Digest, K: AsRef<[u8]> + Ord>(
+    key: &K,
+    range: (&KeyBound<K>, &KeyBound<K>),
+    path: &Path<Vec<u8>>,
+    root: &[u8],
+) -> bool {
+    let (left, right) = range;
+    if !key_between(key, left, right) {
+        return false;
+    }
+    let mut hasher = HashFold::<H, Vec<u8>>::new();
+    match hasher.input(&make_key_range(left, right)) {
+        Ok(leaf) if leaf == path.leaf => (),
+        _ => return false,
+    }
+    path.verify(&root.to_vec(), |l, r| hasher.fold(&l, &r).unwrap())
+}
+
+fn key_between<K: Ord>(key: &K, left: &KeyBound<K>, right: &KeyBound<K>) -> bool {
+    let above = match left {
+        KeyBound::Begin => true,
+        KeyBound::Key(l) => key > l,
+        KeyBound::End => false,
+    };
+    let below = match right {
+        KeyBound::Begin => false,
+        KeyBound::Key(r) => key < r,
+        KeyBound::End => true,
+    };
+    above && below
+}
+
+pub type KeyPathResult<K, T> = (
+    Option<(KeyBound<K>, KeyBound<K>)>,
+    Option<Path<T>>,
+    Option<T>,
+);
+
+pub trait KeyTarget<K> {
+    type Error;
+
+    fn push_key_range(
+        &mut self,
+        left: &KeyBound<K>,
+        right: &KeyBound<K>,
+    ) -> Result<(), Self::Error>;
+}
+
+pub struct KeyTreeFolder<F: TreeFold<Leaf = Vec<u8>>> {
+    pub folder: TreeFolder<F>,
+}
+
+impl<T: TreeFold<Leaf = Vec<u8>>> KeyTreeFolder<T> {
+    pub fn new(base: T) -> Self {
+        Self {
+            folder: TreeFolder::new(base),
+        }
+    }
+
+    pub fn fill(&mut self) -> usize {
+        let fill = make_key_range::<&[u8]>(&KeyBound::End, &KeyBound::End);
+        self.folder.fill(fill).unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.folder.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
+    pub fn result(self) -> Option<T::Target> {
+        let (result, _) = self.folder.result().unwrap();
+        result
+    }
+
+    pub fn complete(self) -> (Option<T::Target>, T) {
+        self.folder.result().unwrap()
+    }
+
+    pub fn update_base<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        self.folder.update_base(f)
+    }
+}
+
+impl<F: TreeFold<Leaf = Vec<u8>>, K: AsRef<[u8]>> KeyTarget<K> for KeyTreeFolder<F> {
+    type Error = F::Error;
+
+    fn push_key_range(
+        &mut self,
+        left: &KeyBound<K>,
+        right: &KeyBound<K>,
+    ) -> Result<(), Self::Error> {
+        self.folder.push(&make_key_range(left, right))
+    }
+}
+
+pub struct KeyPathTracker<T: TreeFold<Leaf = Vec<u8>>, K> {
+    folder: KeyTreeFolder<PathTracker<T>>,
+    find_key: K,
+    range: Option<(KeyBound<K>, KeyBound<K>)>,
+}
+
+impl<T: TreeFold<Leaf = Vec<u8>>, K: Ord> KeyPathTracker<T, K> {
+    pub fn new(base: T, find_key: K) -> Self {
+        Self {
+            folder: KeyTreeFolder::new(PathTracker::new(base, None)),
+            find_key,
+            range: None,
+        }
+    }
+
+    pub fn fill(&mut self) -> usize {
+        self.folder.fill()
+    }
+
+    pub fn len(&self) -> usize {
+        self.folder.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
+    pub fn result(self) -> KeyPathResult<K, T::Target> {
+        let (result, tracker) = self.folder.complete();
+        (self.range, tracker.path_result(), result)
+    }
+}
+
+impl<F, K> KeyTarget<K> for KeyPathTracker<F, K>
+where
+    F: TreeFold<Leaf = Vec<u8>>,
+    K: AsRef<[u8]> + Clone + Ord,
+{
+    type Error = F::Error;
+
+    fn push_key_range(
+        &mut self,
+        left: &KeyBound<K>,
+        right: &KeyBound<K>,
+    ) -> Result<(), Self::Error> {
+        if key_between(&self.find_key, left, right) {
+            self.folder.update_base(|b| b.track_next());
+            self.range.replace((left.clone(), right.clone()));
+        }
+        self.folder.push_key_range(left, right)
+    }
+}
+
+/// Produce leaves from a strictly increasing sequence of keys.
+pub struct KeyParser<K, T: KeyTarget<K>> {
+    pub last: KeyBound<K>,
+    pub target: T,
+}
+
+impl<K: Ord + core::fmt::Debug, T: KeyTarget<K>> KeyParser<K, T>
+where
+    Error: From<T::Error>,
+{
+    pub fn new(target: T) -> Self {
+        Self {
+            last: KeyBound::Begin,
+            target,
+        }
+    }
+
+    pub fn push(&mut self, key: K) -> Result<(), Error> {
+        let key = KeyBound::Key(key);
+        if key <= self.last {
+            return Err(Error::InvalidInput(format!(
+                "unordered or duplicate key: {:?}",
+                key
+            )));
+        }
+        self.target.push_key_range(&self.last, &key)?;
+        self.last = key;
+        Ok(())
+    }
+
+    pub fn extend<I>(&mut self, keys: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        for key in keys {
+            self.push(key)?;
+        }
+        Ok(())
+    }
+
+    pub fn complete(mut self) -> Result<T, Error> {
+        self.target.push_key_range(&self.last, &KeyBound::End)?;
+        Ok(self.target)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use sha2::Sha256;
+
+    fn key(n: u8) -> [u8; 32] {
+        Sha256::digest(&[n]).into()
+    }
+
+    fn sorted_keys(count: u8) -> Vec<[u8; 32]> {
+        let mut keys: Vec<[u8; 32]> = (0..count).map(key).collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_leaves() {
+        struct KeyCollect(Vec<(KeyBound<u32>, KeyBound<u32>)>);
+
+        impl KeyTarget<u32> for KeyCollect {
+            type Error = core::convert::Infallible;
+
+            fn push_key_range(
+                &mut self,
+                left: &KeyBound<u32>,
+                right: &KeyBound<u32>,
+            ) -> Result<(), Self::Error> {
+                self.0.push((left.clone(), right.clone()));
+                Ok(())
+            }
+        }
+
+        let mut parser = KeyParser::new(KeyCollect(vec![]));
+        parser.extend(vec![3, 7]).unwrap();
+        assert!(matches!(parser.push(7), Err(Error::InvalidInput(_))));
+        let collect = parser.complete().unwrap();
+        assert_eq!(
+            collect.0,
+            vec![
+                (KeyBound::Begin, KeyBound::Key(3)),
+                (KeyBound::Key(3), KeyBound::Key(7)),
+                (KeyBound::Key(7), KeyBound::End)
+            ]
+        );
+
+        let empty = KeyParser::<u32, _>::new(KeyCollect(vec![]))
+            .complete()
+            .unwrap();
+        assert_eq!(empty.0, vec![(KeyBound::Begin, KeyBound::End)]);
+    }
+
+    #[test]
+    fn test_sentinels_distinct() {
+        let empty: &[u8] = &[];
+        assert_ne!(
+            make_key_range(&KeyBound::Begin, &KeyBound::Key(empty)),
+            make_key_range::<&[u8]>(&KeyBound::Begin, &KeyBound::End)
+        );
+        assert_ne!(
+            make_key_range(&KeyBound::Key(&[2u8][..]), &KeyBound::End),
+            make_key_range(&KeyBound::End, &KeyBound::Key(&[2u8][..]))
+        );
+    }
+
+    #[test]
+    fn test_non_membership() {
+        let revoked = sorted_keys(5);
+        let mut parser = KeyParser::new(key_hasher::<Sha256>());
+        parser.extend(revoked.clone()).unwrap();
+        let mut hasher = parser.complete().unwrap();
+        hasher.fill();
+        let root = hasher.result().unwrap();
+
+        for n in 0..20 {
+            let find = key(n);
+            let mut parser = KeyParser::new(key_path_hasher::<Sha256, _>(find));
+            parser.extend(revoked.clone()).unwrap();
+            let mut tracker = parser.complete().unwrap();
+            assert_eq!(tracker.len(), revoked.len() + 1);
+            tracker.fill();
+            let (range, path, result) = tracker.result();
+            assert_eq!(result.as_ref(), Some(&root));
+
+            if revoked.contains(&find) {
+                assert!(range.is_none() && path.is_none());
+                continue;
+            }
+            let (left, right) = range.unwrap();
+            let path = path.unwrap();
+            assert!(verify_key_path::<Sha256, _>(
+                &find,
+                (&left, &right),
+                &path,
+                &root
+            ));
+            for other in &revoked {
+                assert!(!verify_key_path::<Sha256, _>(
+                    other,
+                    (&left, &right),
+                    &path,
+                    &root
+                ));
+            }
+        }
+    }
+}
@@ -1,16 +1,86 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "poseidon")]
+pub mod circom;
+mod combine;
+mod consistency;
+#[cfg(feature = "crl")]
+pub mod crl;
+mod dot;
 mod error;
+#[cfg(feature = "eth")]
+pub mod eth;
+#[cfg(feature = "std")]
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "circuit")]
+pub mod gadget;
+#[cfg(feature = "gen")]
+pub mod gen;
 mod hash;
 mod input;
+mod inspect;
+mod kary;
+mod keyed;
+mod mmr;
+#[cfg(feature = "std")]
+mod output;
 mod path;
+#[cfg(feature = "poseidon")]
+pub mod poseidon;
+#[cfg(feature = "python")]
+mod python;
 mod range;
+mod registry;
+pub mod status;
+#[cfg(feature = "status-list")]
+pub mod status_list;
 mod tree;
 
+use alloc::vec::Vec;
+
+pub use combine::{CountingFold, FoldCounts, MapFold, TeeFold};
+pub use consistency::{
+    consistency_ranges, verify_ct_consistency, ConsistencyProof, ConsistencyTracker,
+};
+pub use dot::{bitmap_dot, render_dot, DotTree};
 pub use error::Error;
-pub use hash::Digest;
-pub use input::process_zipped_bits;
-pub use path::{Path, PathJoin};
-pub use range::{range_hasher, range_path_hasher, RangeParser, RangePathTracker, RangeTreeFolder};
-pub use tree::{TreeFold, TreeFolder};
+#[cfg(feature = "std")]
+pub use export::{level_files, level_path, LevelFileWriter, LevelWriter, NodeWriter};
+pub use hash::{Digest, HashFold, Rfc6962Fold};
+pub use input::{process_bitmap, process_block, process_revoked_indices, BitSink};
+#[cfg(feature = "std")]
+pub use input::{
+    process_zipped_bits, process_zipped_reader, process_zipped_reader_from,
+    process_zipped_reader_with, CancelToken, Monitor, Progress,
+};
+#[cfg(feature = "std")]
+pub use inspect::inspect_zipped;
+pub use inspect::{inspect_bitmap, Inspector, RegistryStats};
+pub use kary::{
+    kpath_positions, KLeafPath, KPath, KPathJoin, KPathTracker, KTreeFold, KTreeFolder,
+};
+pub use keyed::{
+    key_hasher, key_path_hasher, make_key_range, verify_key_path, KeyBound, KeyParser,
+    KeyPathResult, KeyPathTracker, KeyTarget, KeyTreeFolder,
+};
+pub use mmr::{bag_peaks, mmr_peak, Bagging, Mmr, MmrAncestryProof, MmrProof};
+#[cfg(feature = "std")]
+pub use output::BitmapWriter;
+pub use path::{path_directions, LeafPath, Path, PathJoin};
+pub use range::{
+    ct_range_hasher, ct_range_path_hasher, make_range, range_hasher, range_path_hasher,
+    verify_range_leaf_path, verify_range_path, RangeCheckpoint, RangeKTreeFolder,
+    RangeLeafPathResult, RangeParser, RangeParserState, RangePathTracker, RangeQueue, RangeTarget,
+    RangeTreeFolder,
+};
+#[cfg(feature = "std")]
+pub use range::{iter_bitmap_ranges, iter_zipped_ranges, RangeIter};
+pub use registry::{RegistryRanges, RevocationRegistry};
+pub use tree::{FinishResult, NodeVisitor, TreeFold, TreeFolder, TreeFrontier};
 
 pub struct HashResult {
     pub leaf_count: usize,
@@ -20,9 +90,42 @@ pub struct HashResult {
 
 pub type HashPath = Path<Vec<u8>>;
 
+pub type MerklePathResult = (Option<(u32, u32)>, Option<HashPath>, HashResult);
+
+#[cfg(feature = "std")]
 pub fn hash_zipped<H: Digest>(path: String, fill: bool) -> Result<HashResult, Error> {
+    hash_zipped_with::<H>(path, fill, &mut Monitor::new())
+}
+
+/// Hash a gzip bitmap file, reporting progress to a `Monitor` which may
+/// also cancel the job.
+#[cfg(feature = "std")]
+pub fn hash_zipped_with<H: Digest>(
+    path: String,
+    fill: bool,
+    monitor: &mut Monitor,
+) -> Result<HashResult, Error> {
+    let target = range_hasher::<H>();
+    let fp = std::fs::File::open(path)?;
+    let mut parsed = process_zipped_reader_with(fp, 0, RangeParser::new(target), monitor)?;
+    let leaf_count = parsed.len();
+    let filled_count = if fill {
+        parsed.fill();
+        parsed.len()
+    } else {
+        leaf_count
+    };
+    let root = parsed.result();
+    Ok(HashResult {
+        leaf_count,
+        filled_count,
+        root,
+    })
+}
+
+pub fn hash_bitmap<H: Digest>(bitmap: &[u8], fill: bool) -> Result<HashResult, Error> {
     let target = range_hasher::<H>();
-    let mut parsed = process_zipped_bits(path, RangeParser::new(target))?;
+    let mut parsed = process_bitmap(bitmap, RangeParser::new(target))?;
     let leaf_count = parsed.len();
     let filled_count = if fill {
         parsed.fill();
@@ -39,14 +142,71 @@ pub fn hash_zipped<H: Digest>(path: String, fill: bool) -> Result<HashResult, Er
 }
 
 // test method exercising PathTracker
-pub fn find_merkle_path<H: Digest>(
+#[cfg(feature = "std")]
+pub fn find_merkle_path<H: Digest>(path: String, index: u32) -> Result<MerklePathResult, Error> {
+    find_merkle_path_with::<H>(path, index, true, &mut Monitor::new())
+}
+
+/// As `find_merkle_path`, but through the unfilled tree, with its peaks
+/// folded from the right.
+#[cfg(feature = "std")]
+pub fn find_merkle_path_unfilled<H: Digest>(
+    path: String,
+    index: u32,
+) -> Result<MerklePathResult, Error> {
+    find_merkle_path_with::<H>(path, index, false, &mut Monitor::new())
+}
+
+#[cfg(feature = "std")]
+pub fn find_merkle_path_with<H: Digest>(
     path: String,
     index: u32,
-) -> Result<(Option<(u32, u32)>, Option<HashPath>, HashResult), Error> {
+    fill: bool,
+    monitor: &mut Monitor,
+) -> Result<MerklePathResult, Error> {
+    let target = range_path_hasher::<H>(index);
+    let fp = std::fs::File::open(path)?;
+    let mut parsed = process_zipped_reader_with(fp, 0, RangeParser::new(target), monitor)?;
+    let leaf_count = parsed.len();
+    if fill {
+        parsed.fill();
+    }
+    let filled_count = parsed.len();
+    let (range, path, root) = parsed.result();
+    Ok((
+        range,
+        path,
+        HashResult {
+            leaf_count,
+            filled_count,
+            root,
+        },
+    ))
+}
+
+pub fn find_bitmap_path<H: Digest>(bitmap: &[u8], index: u32) -> Result<MerklePathResult, Error> {
+    bitmap_path::<H>(bitmap, index, true)
+}
+
+/// As `find_bitmap_path`, but through the unfilled tree.
+pub fn find_bitmap_path_unfilled<H: Digest>(
+    bitmap: &[u8],
+    index: u32,
+) -> Result<MerklePathResult, Error> {
+    bitmap_path::<H>(bitmap, index, false)
+}
+
+fn bitmap_path<H: Digest>(
+    bitmap: &[u8],
+    index: u32,
+    fill: bool,
+) -> Result<MerklePathResult, Error> {
     let target = range_path_hasher::<H>(index);
-    let mut parsed = process_zipped_bits(path, RangeParser::new(target))?;
+    let mut parsed = process_bitmap(bitmap, RangeParser::new(target))?;
     let leaf_count = parsed.len();
-    parsed.fill();
+    if fill {
+        parsed.fill();
+    }
     let filled_count = parsed.len();
     let (range, path, root) = parsed.result();
     Ok((
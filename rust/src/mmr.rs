@@ -0,0 +1,469 @@
+//! Merkle mountain ranges over a growing sequence of leaves.
+//!
+//! The subtrees held by `TreeFolder` are the peaks of the range. Proofs are
+//! made relative to a peak and accompanied by the set of peaks, which may be
+//! bagged into a single root in either order.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::path::{Path, PathJoin};
+use crate::tree::{TreeFold, TreeFolder};
+
+/// The order in which peaks are folded into a single root.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bagging {
+    /// Fold from the smallest peak, as in `TreeFolder::result` and RFC 6962.
+    RightToLeft,
+    /// Fold from the largest peak.
+    LeftToRight,
+}
+
+fn try_bag<T, E, F>(peaks: &[T], order: Bagging, mut f: F) -> Result<Option<T>, E>
+where
+    T: Clone,
+    F: FnMut(&T, &T) -> Result<T, E>,
+{
+    let mut root: Option<T> = None;
+    match order {
+        Bagging::RightToLeft => {
+            for peak in peaks.iter().rev() {
+                root = Some(match root {
+                    Some(acc) => f(peak, &acc)?,
+                    None => peak.clone(),
+                });
+            }
+        }
+        Bagging::LeftToRight => {
+            for peak in peaks {
+                root = Some(match root {
+                    Some(acc) => f(&acc, peak)?,
+                    None => peak.clone(),
+                });
+            }
+        }
+    }
+    Ok(root)
+}
+
+/// Fold a set of peaks into a single root.
+pub fn bag_peaks<T, F>(peaks: &[T], order: Bagging, mut f: F) -> Option<T>
+where
+    T: Clone,
+    F: FnMut(T, T) -> T,
+{
+    try_bag(peaks, order, |a, b| {
+        Result::<_, core::convert::Infallible>::Ok(f(a.clone(), b.clone()))
+    })
+    .unwrap()
+}
+
+/// Locate leaf `index` in a range of `leaf_count` leaves, returning the
+/// position of its peak, the height of the peak and the index of its first
+/// leaf.
+pub fn mmr_peak(index: usize, leaf_count: usize) -> Option<(usize, usize, usize)> {
+    let mut offset = 0;
+    let mut pos = 0;
+    for height in (0..usize::BITS as usize).rev() {
+        if leaf_count >> height & 1 != 0 {
+            if index < offset + (1 << height) {
+                return Some((pos, height, offset));
+            }
+            offset += 1 << height;
+            pos += 1;
+        }
+    }
+    None
+}
+
+fn check_path<T, F>(path: &Path<T>, local: usize, height: usize, peak: &T, f: F) -> bool
+where
+    T: Clone + PartialEq,
+    F: FnMut(T, T) -> T,
+{
+    path.join.len() == height
+        && path
+            .join
+            .iter()
+            .enumerate()
+            .all(|(level, join)| matches!(join, PathJoin::Left(_)) == (local >> level & 1 != 0))
+        && path.verify(peak, f)
+}
+
+/// A path from a leaf to its peak, along with all of the peaks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrProof<T: Clone> {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub path: Path<T>,
+    pub peaks: Vec<T>,
+}
+
+impl<T: Clone + PartialEq> MmrProof<T> {
+    /// Check the path against the peak containing the leaf.
+    pub fn verify_peak<F>(&self, f: F) -> bool
+    where
+        F: FnMut(T, T) -> T,
+    {
+        if self.peaks.len() != self.leaf_count.count_ones() as usize {
+            return false;
+        }
+        match mmr_peak(self.index, self.leaf_count) {
+            Some((pos, height, offset)) => {
+                check_path(&self.path, self.index - offset, height, &self.peaks[pos], f)
+            }
+            None => false,
+        }
+    }
+
+    /// Check the path and that the bagged peaks produce `root`.
+    pub fn verify<F>(&self, root: &T, order: Bagging, mut f: F) -> bool
+    where
+        F: FnMut(T, T) -> T,
+    {
+        self.verify_peak(&mut f) && bag_peaks(&self.peaks, order, f).as_ref() == Some(root)
+    }
+}
+
+/// Paths from each peak of an older range to the peak of a newer range
+/// which contains it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrAncestryProof<T: Clone> {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub paths: Vec<Path<T>>,
+}
+
+impl<T: Clone + PartialEq> MmrAncestryProof<T> {
+    pub fn verify<F>(&self, old_peaks: &[T], new_peaks: &[T], mut f: F) -> bool
+    where
+        F: FnMut(T, T) -> T,
+    {
+        if self.old_size == 0
+            || self.old_size > self.new_size
+            || old_peaks.len() != self.old_size.count_ones() as usize
+            || new_peaks.len() != self.new_size.count_ones() as usize
+            || self.paths.len() != old_peaks.len()
+        {
+            return false;
+        }
+        let mut offset = 0;
+        for (old_peak, path) in old_peaks.iter().zip(&self.paths) {
+            let (_, height, _) = mmr_peak(offset, self.old_size).unwrap();
+            let (pos, new_height, new_offset) = mmr_peak(offset, self.new_size).unwrap();
+            if path.leaf != *old_peak
+                || new_height < height
+                || !check_path(
+                    path,
+                    (offset - new_offset) >> height,
+                    new_height - height,
+                    &new_peaks[pos],
+                    &mut f,
+                )
+            {
+                return false;
+            }
+            offset += 1 << height;
+        }
+        true
+    }
+}
+
+struct Tracked<T: Clone> {
+    start: usize,
+    top: (usize, usize),
+    path: Option<Path<T>>,
+}
+
+/// Extends the paths from tracked nodes as leaves are folded.
+struct MmrTracker<T: TreeFold> {
+    base: T,
+    input_index: usize,
+    stack: Vec<(usize, usize)>,
+    leaves: Vec<Tracked<T::Target>>,
+    peaks: Vec<Tracked<T::Target>>,
+    peaks_size: usize,
+}
+
+impl<T: TreeFold> TreeFold for MmrTracker<T> {
+    type Leaf = T::Leaf;
+    type Target = T::Target;
+    type Error = T::Error;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        let r = self.base.input(leaf)?;
+        let index = self.input_index;
+        for tracked in self.leaves.iter_mut() {
+            if tracked.start == index {
+                tracked.path.replace(Path::new(r.clone(), vec![]));
+            }
+        }
+        self.stack.push((index, index + 1));
+        self.input_index += 1;
+        Ok(r)
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        let r = self.base.fold(a, b)?;
+        let right = self.stack.pop().unwrap();
+        let left = self.stack.pop().unwrap();
+        let joined = (left.0, right.1);
+        for tracked in self.leaves.iter_mut().chain(self.peaks.iter_mut()) {
+            if let Some(path) = tracked.path.as_mut() {
+                if tracked.top == left {
+                    path.join_right(b.clone());
+                    tracked.top = joined;
+                } else if tracked.top == right {
+                    path.join_left(a.clone());
+                    tracked.top = joined;
+                }
+            }
+        }
+        self.stack.push(joined);
+        Ok(r)
+    }
+}
+
+/// An append-only Merkle mountain range, which tracks proofs for chosen
+/// leaves and for its earlier peaks as leaves are added.
+pub struct Mmr<T: TreeFold> {
+    folder: TreeFolder<MmrTracker<T>>,
+}
+
+impl<T: TreeFold> Mmr<T> {
+    pub fn new(base: T) -> Self {
+        Self {
+            folder: TreeFolder::new(MmrTracker {
+                base,
+                input_index: 0,
+                stack: vec![],
+                leaves: vec![],
+                peaks: vec![],
+                peaks_size: 0,
+            }),
+        }
+    }
+
+    pub fn push(&mut self, leaf: &T::Leaf) -> Result<(), T::Error> {
+        self.folder.push(leaf)
+    }
+
+    pub fn extend<L>(&mut self, leaves: L) -> Result<(), T::Error>
+    where
+        L: IntoIterator<Item = T::Leaf>,
+    {
+        self.folder.extend(leaves)
+    }
+
+    pub fn len(&self) -> usize {
+        self.folder.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
+    pub fn peaks(&self) -> &[T::Target] {
+        self.folder.peaks()
+    }
+
+    /// Bag the current peaks into a single root.
+    pub fn root(&mut self, order: Bagging) -> Result<Option<T::Target>, T::Error> {
+        let peaks = self.folder.peaks().to_vec();
+        let mut result = Ok(None);
+        self.folder.update_base(|tracker| {
+            result = try_bag(&peaks, order, |a, b| tracker.base.fold(a, b));
+        });
+        result
+    }
+
+    /// Track the path for a leaf which has not yet been added. Returns
+    /// `false` if the leaf has already been added.
+    pub fn track_leaf(&mut self, index: usize) -> bool {
+        if index < self.len() {
+            return false;
+        }
+        self.folder.update_base(|tracker| {
+            tracker.leaves.push(Tracked {
+                start: index,
+                top: (index, index + 1),
+                path: None,
+            })
+        });
+        true
+    }
+
+    /// The proof for a tracked leaf, relative to the current peaks.
+    pub fn proof(&self, index: usize) -> Option<MmrProof<T::Target>> {
+        let path = self
+            .folder
+            .base()
+            .leaves
+            .iter()
+            .find(|tracked| tracked.start == index)?
+            .path
+            .clone()?;
+        Some(MmrProof {
+            index,
+            leaf_count: self.len(),
+            path,
+            peaks: self.peaks().to_vec(),
+        })
+    }
+
+    /// Begin tracking the current peaks, replacing any peaks tracked before,
+    /// for a later `ancestry_proof`.
+    pub fn track_peaks(&mut self) {
+        let peaks = self.folder.peaks().to_vec();
+        let size = self.len();
+        self.folder.update_base(|tracker| {
+            tracker.peaks = tracker
+                .stack
+                .iter()
+                .zip(peaks)
+                .map(|(range, peak)| Tracked {
+                    start: range.0,
+                    top: *range,
+                    path: Some(Path::new(peak, vec![])),
+                })
+                .collect();
+            tracker.peaks_size = size;
+        });
+    }
+
+    /// Prove that the peaks tracked by `track_peaks` are contained in the
+    /// current peaks.
+    pub fn ancestry_proof(&self) -> Option<MmrAncestryProof<T::Target>> {
+        let tracker = self.folder.base();
+        if tracker.peaks_size == 0 {
+            return None;
+        }
+        let paths = tracker
+            .peaks
+            .iter()
+            .map(|tracked| tracked.path.clone())
+            .collect::<Option<Vec<_>>>()?;
+        Some(MmrAncestryProof {
+            old_size: tracker.peaks_size,
+            new_size: self.len(),
+            paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tree::test::TestFold;
+    use alloc::string::{String, ToString};
+
+    fn test_fold(l: String, r: String) -> String {
+        format!("[{},{}]", l, r)
+    }
+
+    #[test]
+    fn test_bagging() {
+        let leaves: Vec<String> = (0..7).map(|n| n.to_string()).collect();
+        let mut mmr = Mmr::new(TestFold {});
+        mmr.extend(leaves.clone()).unwrap();
+        assert_eq!(mmr.peaks(), &["[[0,1],[2,3]]", "[4,5]", "6"]);
+        assert_eq!(
+            mmr.root(Bagging::RightToLeft).unwrap().unwrap(),
+            "[[[0,1],[2,3]],[[4,5],6]]"
+        );
+        assert_eq!(
+            mmr.root(Bagging::LeftToRight).unwrap().unwrap(),
+            "[[[[0,1],[2,3]],[4,5]],6]"
+        );
+        let (result, _) = TreeFolder::fold(TestFold {}, leaves, None).unwrap();
+        assert_eq!(mmr.root(Bagging::RightToLeft).unwrap(), result);
+        assert_eq!(
+            bag_peaks::<String, _>(&[], Bagging::LeftToRight, test_fold),
+            None
+        );
+    }
+
+    #[test]
+    fn test_peak() {
+        assert_eq!(mmr_peak(0, 7), Some((0, 2, 0)));
+        assert_eq!(mmr_peak(5, 7), Some((1, 1, 4)));
+        assert_eq!(mmr_peak(6, 7), Some((2, 0, 6)));
+        assert_eq!(mmr_peak(7, 7), None);
+    }
+
+    #[test]
+    fn test_proof() {
+        for size in 1..24usize {
+            let mut mmr = Mmr::new(TestFold {});
+            for index in 0..size {
+                assert!(mmr.track_leaf(index));
+                mmr.push(&index.to_string()).unwrap();
+            }
+            assert!(!mmr.track_leaf(0));
+            for order in &[Bagging::RightToLeft, Bagging::LeftToRight] {
+                let root = mmr.root(*order).unwrap().unwrap();
+                for index in 0..size {
+                    let proof = mmr.proof(index).unwrap();
+                    assert!(proof.verify(&root, *order, test_fold), "{} {}", size, index);
+
+                    let mut moved = proof.clone();
+                    moved.index = index ^ 1;
+                    if moved.index < size && !proof.path.join.is_empty() {
+                        assert!(!moved.verify_peak(test_fold));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ancestry() {
+        for old_size in 1..20usize {
+            for new_size in old_size..24 {
+                let mut mmr = Mmr::new(TestFold {});
+                mmr.extend((0..old_size).map(|n| n.to_string())).unwrap();
+                let old_peaks = mmr.peaks().to_vec();
+                mmr.track_peaks();
+                mmr.extend((old_size..new_size).map(|n| n.to_string()))
+                    .unwrap();
+                let proof = mmr.ancestry_proof().unwrap();
+                assert_eq!((proof.old_size, proof.new_size), (old_size, new_size));
+                assert!(
+                    proof.verify(&old_peaks, mmr.peaks(), test_fold),
+                    "{} {}",
+                    old_size,
+                    new_size
+                );
+
+                let mut forged = old_peaks.clone();
+                forged[0] = "X".to_string();
+                assert!(!proof.verify(&forged, mmr.peaks(), test_fold));
+            }
+        }
+
+        let mmr = Mmr::new(TestFold {});
+        assert!(mmr.ancestry_proof().is_none());
+    }
+
+    #[test]
+    fn test_hash_mmr() {
+        use crate::hash::{Digest, HashFold};
+        use sha2::Sha256;
+
+        let mut mmr = Mmr::new(HashFold::<Sha256, [u8; 8]>::new());
+        assert!(mmr.track_leaf(9));
+        for day in 0..12u64 {
+            mmr.push(&day.to_be_bytes()).unwrap();
+        }
+        let root = mmr.root(Bagging::LeftToRight).unwrap().unwrap();
+        let proof = mmr.proof(9).unwrap();
+        assert_eq!(
+            proof.path.leaf,
+            Sha256::digest(&9u64.to_be_bytes()).to_vec()
+        );
+        let mut hasher = HashFold::<Sha256, [u8; 8]>::new();
+        assert!(proof.verify(&root, Bagging::LeftToRight, |l, r| hasher
+            .fold(&l, &r)
+            .unwrap()));
+    }
+}
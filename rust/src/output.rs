@@ -0,0 +1,178 @@
+use std::io::Write;
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::error::Error;
+use crate::input::{BitBlock, BitSink};
+
+const BLOCK_BITS: u32 = (core::mem::size_of::<BitBlock>() * 8) as u32;
+
+/// Write the gzip bitmap format read by `process_zipped_bits`, with each
+/// block of bits stored most significant bit first in big-endian order.
+///
+/// As a `BitSink` it accepts single bits or runs, and it may be fed from
+/// sorted revoked indices with `process_revoked_indices`.
+pub struct BitmapWriter<W: Write> {
+    encoder: GzEncoder<W>,
+    block: BitBlock,
+    block_len: u32,
+    written: u64,
+    size: Option<u64>,
+}
+
+impl<W: Write> BitmapWriter<W> {
+    pub fn new(target: W) -> Self {
+        Self {
+            encoder: GzEncoder::new(target, Compression::default()),
+            block: 0,
+            block_len: 0,
+            written: 0,
+            size: None,
+        }
+    }
+
+    /// Pad the output with non-revoked bits up to a total number of bits
+    /// when complete.
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.size.replace(size);
+        self
+    }
+
+    /// The number of bits accepted so far.
+    pub fn len(&self) -> u64 {
+        self.written + self.block_len as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, revoked: bool, count: u32) -> Result<(), Error> {
+        let fill = if revoked { BitBlock::MAX } else { 0 };
+        self.block |= (fill >> (BLOCK_BITS - count)) << (BLOCK_BITS - self.block_len - count);
+        self.block_len += count;
+        if self.block_len == BLOCK_BITS {
+            self.encoder.write_all(&self.block.to_be_bytes())?;
+            self.written += BLOCK_BITS as u64;
+            self.block = 0;
+            self.block_len = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> BitSink for BitmapWriter<W> {
+    type Result = W;
+
+    fn process_bits(&mut self, revoked: bool, mut count: u32) -> Result<(), Error> {
+        while count > 0 {
+            let take = count.min(BLOCK_BITS - self.block_len);
+            self.push(revoked, take)?;
+            count -= take;
+        }
+        Ok(())
+    }
+
+    fn complete(mut self) -> Result<Self::Result, Error> {
+        if let Some(size) = self.size {
+            let len = self.len();
+            if len > size {
+                return Err(Error::InvalidInput(format!(
+                    "bitmap of {} bits exceeds size {}",
+                    len, size
+                )));
+            }
+            let mut remain = size - len;
+            while remain > 0 {
+                let take = remain.min(u32::MAX as u64) as u32;
+                self.process_bits(false, take)?;
+                remain -= take as u64;
+            }
+        }
+        if self.block_len > 0 {
+            let bytes = self.block_len.div_ceil(8) as usize;
+            self.encoder.write_all(&self.block.to_be_bytes()[..bytes])?;
+        }
+        Ok(self.encoder.finish()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::{process_revoked_indices, process_zipped_bits, process_zipped_reader};
+    use crate::range::{RangeParser, RangeQueue};
+    use crate::registry::RevocationRegistry;
+    use std::fs;
+
+    fn unzip(zipped: &[u8]) -> Vec<u8> {
+        RevocationRegistry::load(zipped)
+            .unwrap()
+            .as_bitmap()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_bits() {
+        let mut writer = BitmapWriter::new(vec![]);
+        for bit in &[true, false, false, false, false, false, true, true, true] {
+            writer.process_bits(*bit, 1).unwrap();
+        }
+        assert_eq!(writer.len(), 9);
+        assert_eq!(unzip(&writer.complete().unwrap()), vec![0x83, 0x80]);
+    }
+
+    #[test]
+    fn test_runs() {
+        let mut writer = BitmapWriter::new(vec![]).with_size(256);
+        writer.process_bits(false, 60).unwrap();
+        writer.process_bits(true, 72).unwrap();
+        writer.process_bits(false, 1).unwrap();
+        writer.process_bits(true, 1).unwrap();
+        let bitmap = unzip(&writer.complete().unwrap());
+        let mut expect = vec![0u8; 32];
+        expect[7] = 0x0f;
+        expect[8..16].copy_from_slice(&[0xff; 8]);
+        expect[16] = 0xf4;
+        assert_eq!(bitmap, expect);
+
+        let mut writer = BitmapWriter::new(vec![]).with_size(8);
+        writer.process_bits(true, 9).unwrap();
+        assert!(matches!(writer.complete(), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let revoked = vec![1, 2, 64, 65, 66, 200, 1000, 1001, 4096];
+        let writer = BitmapWriter::new(vec![]).with_size(4096);
+        let zipped = process_revoked_indices(revoked.clone(), writer).unwrap();
+
+        let mut registry = RevocationRegistry::new(4096);
+        for index in &revoked {
+            registry.revoke(*index).unwrap();
+        }
+        assert_eq!(unzip(&zipped), registry.as_bitmap());
+
+        let path = std::env::temp_dir().join(format!("brt-roundtrip-{}.gz", std::process::id()));
+        fs::write(&path, &zipped).unwrap();
+        let parser = RangeParser::new(RangeQueue::default());
+        let collect = process_zipped_bits(path.to_string_lossy().into_owned(), parser).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(collect.ranges, registry.ranges().collect::<Vec<_>>());
+
+        // re-encode the ranges as runs of bits
+        let mut writer = BitmapWriter::new(vec![]);
+        let mut next = 1;
+        for (left, right) in &collect.ranges {
+            writer.process_bits(true, left + 1 - next).unwrap();
+            writer
+                .process_bits(false, right.min(&4097) - left - 1)
+                .unwrap();
+            next = *right;
+        }
+        let rezipped = writer.complete().unwrap();
+        let parser = RangeParser::new(RangeQueue::default());
+        let recollect = process_zipped_reader(rezipped.as_slice(), parser).unwrap();
+        assert_eq!(recollect.ranges, collect.ranges);
+    }
+}
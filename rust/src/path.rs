@@ -1,3 +1,6 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
 use super::tree::TreeFold;
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -21,6 +24,11 @@ impl<T: Clone> Path<T> {
         1 + self.join.len()
     }
 
+    /// True for the path of a single-leaf tree, which has no joins.
+    pub fn is_empty(&self) -> bool {
+        self.join.is_empty()
+    }
+
     pub fn join_left(&mut self, result: T) {
         self.join.push(PathJoin::Left(result))
     }
@@ -42,6 +50,85 @@ impl<T: Clone> Path<T> {
         }
         result
     }
+
+    pub fn verify<F>(&self, root: &T, f: F) -> bool
+    where
+        T: PartialEq,
+        F: FnMut(T, T) -> T,
+    {
+        self.clone().fold(f) == *root
+    }
+}
+
+/// The sibling directions on the path from leaf `index` to the root of a
+/// tree built by `TreeFolder` from `leaf_count` leaves, starting from the
+/// leaf, where `true` indicates a sibling on the left. When the tree is not
+/// filled, the complete subtrees left by `TreeFolder` are joined from the
+/// right.
+pub fn path_directions(index: usize, leaf_count: usize) -> Option<Vec<bool>> {
+    if index >= leaf_count {
+        return None;
+    }
+    let peaks: Vec<usize> = (0..usize::BITS as usize)
+        .rev()
+        .filter(|height| leaf_count >> height & 1 != 0)
+        .collect();
+    let mut offset = 0;
+    for (pos, height) in peaks.iter().enumerate() {
+        let size = 1 << height;
+        if index < offset + size {
+            let local = index - offset;
+            let mut dirs: Vec<bool> = (0..*height).map(|b| local >> b & 1 != 0).collect();
+            if pos + 1 < peaks.len() {
+                dirs.push(false);
+            }
+            dirs.resize(dirs.len() + pos, true);
+            return Some(dirs);
+        }
+        offset += size;
+    }
+    None
+}
+
+/// A `Path` along with the position of its leaf and the number of leaves
+/// in the tree, filled or unfilled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeafPath<T: Clone> {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub path: Path<T>,
+}
+
+impl<T: Clone> LeafPath<T> {
+    pub fn new(index: usize, leaf_count: usize, path: Path<T>) -> Self {
+        Self {
+            index,
+            leaf_count,
+            path,
+        }
+    }
+
+    /// Check that the join directions are those of the leaf position.
+    pub fn check_directions(&self) -> bool {
+        match path_directions(self.index, self.leaf_count) {
+            Some(dirs) => {
+                dirs.len() == self.path.join.len()
+                    && dirs
+                        .iter()
+                        .zip(&self.path.join)
+                        .all(|(left, join)| *left == matches!(join, PathJoin::Left(_)))
+            }
+            None => false,
+        }
+    }
+
+    pub fn verify<F>(&self, root: &T, f: F) -> bool
+    where
+        T: PartialEq,
+        F: FnMut(T, T) -> T,
+    {
+        self.check_directions() && self.path.verify(root, f)
+    }
 }
 
 pub struct PathTracker<T: TreeFold> {
@@ -71,6 +158,14 @@ impl<T: TreeFold> PathTracker<T> {
         self.path.clone()
     }
 
+    /// The path with its leaf position, given the final number of leaves.
+    pub fn leaf_path_result(&self, leaf_count: usize) -> Option<LeafPath<T::Target>> {
+        let index = self.track_input_index?;
+        self.path
+            .clone()
+            .map(|path| LeafPath::new(index, leaf_count, path))
+    }
+
     pub fn track_index(&mut self, index: usize) {
         // FIXME raise error if index >= input_index
         self.track_input_index.replace(index);
@@ -163,6 +258,40 @@ mod test {
         assert_eq!(path.fold(|l, r| format!("[{},{}]", l, r)), expect_result);
     }
 
+    #[test]
+    fn test_leaf_path() {
+        for leaf_count in 1..40usize {
+            let leaves: Vec<String> = (0..leaf_count).map(|n| n.to_string()).collect();
+            for fill in &[None, Some("E".to_string())] {
+                let filled_count = if fill.is_some() {
+                    leaf_count.next_power_of_two()
+                } else {
+                    leaf_count
+                };
+                for i in 0..leaf_count {
+                    let tracker = PathTracker::new(TestFold {}, Some(i));
+                    let (result, tracker) =
+                        TreeFolder::fold(tracker, leaves.clone(), fill.clone()).unwrap();
+                    let result = result.unwrap();
+                    let path = tracker.leaf_path_result(filled_count).unwrap();
+                    assert_eq!(path.index, i);
+                    assert!(path.check_directions(), "{} {}", leaf_count, i);
+                    assert!(path.verify(&result, |l, r| format!("[{},{}]", l, r)));
+
+                    // the same path does not match a neighbouring position
+                    let mut moved = path.clone();
+                    moved.index ^= 1;
+                    if moved.index < filled_count && !path.path.join.is_empty() {
+                        assert!(!moved.check_directions());
+                    }
+                }
+            }
+        }
+        assert_eq!(path_directions(5, 5), None);
+        assert_eq!(path_directions(4, 5), Some(vec![true]));
+        assert_eq!(path_directions(2, 5), Some(vec![false, true, false]));
+    }
+
     #[test]
     fn test_track_multiple() {
         let leaves: Vec<String> = (0..128).map(|n| n.to_string()).collect();
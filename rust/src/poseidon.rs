@@ -0,0 +1,151 @@
+use ff::PrimeField;
+use neptune::poseidon::{Arity, HashMode, Poseidon, PoseidonConstants};
+use neptune::scalar_from_u64;
+use paired::bls12_381::{Bls12, Fr};
+
+use crate::kary::KTreeFold;
+use crate::tree::TreeFold;
+
+/// Poseidon hashing of range leaves, with `A` inputs per node. A fold of
+/// fewer than `A` children, such as a binary fold or the join of the peaks
+/// of an unfilled k-ary tree, pads the missing inputs with zero field
+/// elements, so the node hashes the same as one with trailing zero children.
+pub struct PoseidonFold<'a, A: Arity<Fr>> {
+    hasher: Poseidon<'a, Bls12, A>,
+}
+
+impl<'a, A: Arity<Fr>> PoseidonFold<'a, A> {
+    pub fn new(constants: &'a PoseidonConstants<Bls12, A>) -> Self {
+        Self {
+            hasher: Poseidon::new(constants),
+        }
+    }
+
+    fn hash(&mut self, inputs: &[Fr]) -> Fr {
+        // missing inputs are left as zero, padding a partial group
+        self.hasher.reset();
+        for input in inputs {
+            self.hasher.input(*input).unwrap();
+        }
+        self.hasher.hash_in_mode(HashMode::OptimizedStatic)
+    }
+}
+
+/// The field element for a range leaf, `left * 2^32 + right`.
+pub fn range_leaf_scalar(leaf: &[u8; 8]) -> Fr {
+    scalar_from_u64(u64::from_be_bytes(*leaf))
+}
+
+impl<A: Arity<Fr>> TreeFold for PoseidonFold<'_, A> {
+    type Leaf = [u8; 8];
+    type Target = Fr;
+    type Error = std::convert::Infallible;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        Ok(range_leaf_scalar(leaf))
+    }
+
+    fn fold(&mut self, a: &Self::Target, b: &Self::Target) -> Result<Self::Target, Self::Error> {
+        Ok(self.hash(&[*a, *b]))
+    }
+}
+
+impl<A: Arity<Fr>> KTreeFold for PoseidonFold<'_, A> {
+    type Leaf = [u8; 8];
+    type Target = Fr;
+    type Error = std::convert::Infallible;
+
+    fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error> {
+        Ok(range_leaf_scalar(leaf))
+    }
+
+    fn fold(&mut self, children: &[Self::Target]) -> Result<Self::Target, Self::Error> {
+        Ok(self.hash(children))
+    }
+}
+
+/// Format a field element as a decimal string.
+pub fn fr_to_decimal(value: &Fr) -> String {
+    const CHUNK: u64 = 10_000_000_000_000_000_000;
+    let mut limbs: Vec<u64> = value.into_repr().as_ref().to_vec();
+    let mut chunks = vec![];
+    while limbs.iter().any(|limb| *limb != 0) {
+        let mut rem: u128 = 0;
+        for limb in limbs.iter_mut().rev() {
+            let cur = (rem << 64) | (*limb as u128);
+            *limb = (cur / CHUNK as u128) as u64;
+            rem = cur % CHUNK as u128;
+        }
+        chunks.push(rem as u64);
+    }
+    match chunks.split_last() {
+        None => "0".to_string(),
+        Some((first, rest)) => {
+            let mut result = first.to_string();
+            for chunk in rest.iter().rev() {
+                result.push_str(&format!("{:019}", chunk));
+            }
+            result
+        }
+    }
+}
+
+/// Parse a field element from a decimal string.
+pub fn fr_from_decimal(value: &str) -> Option<Fr> {
+    let mut limbs = [0u64; 4];
+    if value.is_empty() {
+        return None;
+    }
+    for digit in value.chars() {
+        let mut carry = digit.to_digit(10)? as u128;
+        for limb in limbs.iter_mut() {
+            let cur = (*limb as u128) * 10 + carry;
+            *limb = cur as u64;
+            carry = cur >> 64;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    let mut repr = <Fr as PrimeField>::Repr::default();
+    repr.as_mut().copy_from_slice(&limbs);
+    Fr::from_repr(repr).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::{RangeKTreeFolder, RangeTarget, RangeTreeFolder};
+    use generic_array::typenum::U2;
+
+    #[test]
+    fn test_decimal() {
+        for value in &[0u64, 1, 9, 10, 1 << 40, u64::MAX] {
+            let fr: Fr = scalar_from_u64(*value);
+            assert_eq!(fr_to_decimal(&fr), value.to_string());
+            assert_eq!(fr_from_decimal(&value.to_string()), Some(fr));
+        }
+        let mut fr: Fr = scalar_from_u64(u64::MAX);
+        ff::Field::square(&mut fr);
+        assert_eq!(
+            fr_to_decimal(&fr),
+            "340282366920938463426481119284349108225"
+        );
+        assert_eq!(fr_from_decimal(&fr_to_decimal(&fr)), Some(fr));
+        assert_eq!(fr_from_decimal("12a"), None);
+    }
+
+    #[test]
+    fn test_binary_matches_kary() {
+        let constants = PoseidonConstants::<Bls12, U2>::new();
+        let mut binary = RangeTreeFolder::new(PoseidonFold::new(&constants));
+        let mut kary = RangeKTreeFolder::new(PoseidonFold::new(&constants), 2);
+        for (left, right) in &[(0, 3), (3, 8), (9, 12), (12, u32::MAX)] {
+            binary.push_range(*left, *right).unwrap();
+            kary.push_range(*left, *right).unwrap();
+        }
+        binary.fill();
+        kary.fill();
+        assert_eq!(binary.result(), kary.result());
+    }
+}
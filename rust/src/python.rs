@@ -0,0 +1,329 @@
+use pyo3::exceptions::{PyIOError, PyRuntimeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyIterator};
+use sha2::Sha256;
+use std::collections::VecDeque;
+
+use crate::error::Error;
+use crate::hash::Digest;
+use crate::input::{process_zipped_bits, BitSink};
+use crate::path::{Path, PathJoin};
+use crate::range::{make_range, range_hasher, RangeParser, RangeQueue, RangeTarget};
+use crate::HashResult;
+
+type PyPath = (PyObject, Vec<(&'static str, PyObject)>);
+
+type PyPathResult = (Option<(u32, u32)>, Option<PyPath>, PyObject);
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => PyIOError::new_err(err.to_string()),
+            err => PyRuntimeError::new_err(err.to_string()),
+        }
+    }
+}
+
+fn fill_ranges(ranges: &mut Vec<(u32, u32)>) {
+    let fill_size = ranges.len().next_power_of_two();
+    ranges.resize(fill_size, (u32::MAX, u32::MAX));
+}
+
+/// Pull-based leaf generation, fed one revocation bit at a time
+struct LeafQueue {
+    parser: Option<RangeParser<RangeQueue>>,
+    remain: VecDeque<(u32, u32)>,
+    fill: bool,
+    count: usize,
+    fill_remain: usize,
+}
+
+impl LeafQueue {
+    fn new(fill: bool) -> Self {
+        Self {
+            parser: Some(RangeParser::new(RangeQueue::default())),
+            remain: VecDeque::new(),
+            fill,
+            count: 0,
+            fill_remain: 0,
+        }
+    }
+
+    fn push_bit(&mut self, revoked: bool) -> Result<(), Error> {
+        match self.parser.as_mut() {
+            Some(parser) => parser.process_bits(revoked, 1),
+            None => Err(Error::InvalidInput("bits already finished".to_string())),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(parser) = self.parser.take() {
+            self.remain = parser.complete()?.ranges;
+            let total = self.count + self.remain.len();
+            if self.fill {
+                self.fill_remain = total.next_power_of_two() - total;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.parser.is_none()
+    }
+
+    fn pop(&mut self) -> Option<(u32, u32)> {
+        let next = match self.parser.as_mut() {
+            Some(parser) => parser.target.ranges.pop_front(),
+            None => self.remain.pop_front(),
+        };
+        if next.is_some() {
+            self.count += 1;
+            return next;
+        }
+        if self.fill_remain > 0 {
+            self.fill_remain -= 1;
+            return Some((u32::MAX, u32::MAX));
+        }
+        None
+    }
+}
+
+/// Iterator over the `(left, right)` leaves for a sequence of revocation bits
+#[pyclass]
+struct LeafIter {
+    bits: Py<PyIterator>,
+    leaves: LeafQueue,
+}
+
+#[pymethods]
+impl LeafIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>, py: Python) -> PyResult<Option<(u32, u32)>> {
+        loop {
+            if let Some(leaf) = slf.leaves.pop() {
+                return Ok(Some(leaf));
+            }
+            if slf.leaves.is_finished() {
+                return Ok(None);
+            }
+            let bit = match slf.bits.as_ref(py).next() {
+                Some(bit) => Some(bit?.is_true()?),
+                None => None,
+            };
+            match bit {
+                Some(bit) => slf.leaves.push_bit(bit)?,
+                None => slf.leaves.finish()?,
+            }
+        }
+    }
+}
+
+fn path_joins(parts: Vec<(String, Vec<u8>)>) -> Result<Vec<PathJoin<Vec<u8>>>, Error> {
+    parts
+        .into_iter()
+        .map(|(pfx, hash)| match pfx.as_str() {
+            "L" => Ok(PathJoin::Left(hash)),
+            "R" => Ok(PathJoin::Right(hash)),
+            _ => Err(Error::InvalidInput("invalid path direction".to_string())),
+        })
+        .collect()
+}
+
+fn result_dict(py: Python, result: HashResult) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("leaf_count", result.leaf_count)?;
+    dict.set_item("leaf_count_filled", result.filled_count)?;
+    dict.set_item("root", result.root.map(|root| PyBytes::new(py, &root)))?;
+    Ok(dict.into())
+}
+
+fn path_tuple(py: Python, path: Path<Vec<u8>>) -> PyPath {
+    let join = path
+        .join
+        .into_iter()
+        .map(|part| match part {
+            PathJoin::Left(h) => ("L", PyBytes::new(py, &h).into()),
+            PathJoin::Right(h) => ("R", PyBytes::new(py, &h).into()),
+        })
+        .collect();
+    (PyBytes::new(py, &path.leaf).into(), join)
+}
+
+fn branch_hash(l: Vec<u8>, r: Vec<u8>) -> Vec<u8> {
+    let mut hash = Sha256::new();
+    hash.input(l);
+    hash.input(r);
+    hash.result().to_vec()
+}
+
+/// Hash a gzipped bitmap file, returning the same dict as `hash_leaves`
+#[pyfunction]
+#[pyo3(signature = (path, fill = true))]
+fn hash_zipped(py: Python, path: String, fill: bool) -> PyResult<PyObject> {
+    let result = py.allow_threads(|| crate::hash_zipped::<Sha256>(path, fill))?;
+    result_dict(py, result)
+}
+
+/// Hash a sequence of `(left, right)` leaves, returning the same dict as `hash_leaves`
+#[pyfunction]
+#[pyo3(signature = (leaves, fill = true))]
+fn hash_leaves(py: Python, leaves: &PyAny, fill: bool) -> PyResult<PyObject> {
+    let mut target = range_hasher::<Sha256>();
+    for leaf in leaves.iter()? {
+        let (left, right): (u32, u32) = leaf?.extract()?;
+        target.push_range(left, right)?;
+    }
+    let leaf_count = target.len();
+    if fill {
+        target.fill();
+    }
+    let filled_count = target.len();
+    let root = target.result();
+    result_dict(
+        py,
+        HashResult {
+            leaf_count,
+            filled_count,
+            root,
+        },
+    )
+}
+
+/// Find the range containing a non-revoked index, and the hash chain for its leaf
+#[pyfunction]
+#[pyo3(signature = (path, index, fill = true))]
+fn find_merkle_path(py: Python, path: String, index: u32, fill: bool) -> PyResult<PyPathResult> {
+    let (range, path, result) = py.allow_threads(|| {
+        if fill {
+            crate::find_merkle_path::<Sha256>(path, index)
+        } else {
+            crate::find_merkle_path_unfilled::<Sha256>(path, index)
+        }
+    })?;
+    Ok((
+        range,
+        path.map(|path| path_tuple(py, path)),
+        result_dict(py, result)?,
+    ))
+}
+
+/// Check a hash chain produced by `find_merkle_path` against a range and root
+#[pyfunction]
+fn verify_merkle_path(
+    range: (u32, u32),
+    path: (Vec<u8>, Vec<(String, Vec<u8>)>),
+    root: Vec<u8>,
+) -> PyResult<bool> {
+    let (leaf, parts) = path;
+    if Sha256::digest(&make_range(range.0, range.1)).as_slice() != leaf.as_slice() {
+        return Ok(false);
+    }
+    let join = path_joins(parts)?;
+    Ok(Path::new(leaf, join).fold(branch_hash) == root)
+}
+
+/// Produce the `(left, right)` leaves for a sequence of revocation bits
+#[pyfunction]
+#[pyo3(signature = (bits, fill = false))]
+fn gen_leaves(bits: &PyAny, fill: bool) -> PyResult<LeafIter> {
+    Ok(LeafIter {
+        bits: bits.iter()?.into(),
+        leaves: LeafQueue::new(fill),
+    })
+}
+
+/// Produce the `(left, right)` leaves for a gzipped bitmap file
+#[pyfunction]
+#[pyo3(signature = (path, fill = false))]
+fn zipped_leaves(py: Python, path: String, fill: bool) -> PyResult<Vec<(u32, u32)>> {
+    let mut ranges: Vec<_> = py
+        .allow_threads(|| process_zipped_bits(path, RangeParser::new(RangeQueue::default())))?
+        .ranges
+        .into();
+    if fill {
+        fill_ranges(&mut ranges);
+    }
+    Ok(ranges)
+}
+
+#[pymodule]
+#[pyo3(name = "_native")]
+fn native_module(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(hash_zipped, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(find_merkle_path, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_merkle_path, m)?)?;
+    m.add_function(wrap_pyfunction!(gen_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(zipped_leaves, m)?)?;
+    m.add_class::<LeafIter>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn queue_leaves(bits: &[bool], fill: bool) -> Vec<(u32, u32)> {
+        let mut leaves = LeafQueue::new(fill);
+        let mut result = vec![];
+        for &bit in bits {
+            leaves.push_bit(bit).unwrap();
+            while let Some(leaf) = leaves.pop() {
+                result.push(leaf);
+            }
+        }
+        leaves.finish().unwrap();
+        while let Some(leaf) = leaves.pop() {
+            result.push(leaf);
+        }
+        result
+    }
+
+    #[test]
+    fn test_leaf_queue() {
+        let bits = [false, true, false, false, true, true, false];
+        let mut parser = RangeParser::new(RangeQueue::default());
+        for &bit in &bits {
+            parser.process_bits(bit, 1).unwrap();
+        }
+        let mut expect: Vec<_> = parser.complete().unwrap().ranges.into();
+        assert_eq!(queue_leaves(&bits, false), expect);
+        fill_ranges(&mut expect);
+        assert_eq!(queue_leaves(&bits, true), expect);
+    }
+
+    #[test]
+    fn test_leaf_queue_empty() {
+        assert_eq!(queue_leaves(&[], false), vec![(0, u32::MAX)]);
+        assert_eq!(queue_leaves(&[], true), vec![(0, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_leaf_queue_finished() {
+        let mut leaves = LeafQueue::new(false);
+        leaves.finish().unwrap();
+        assert!(leaves.is_finished());
+        assert!(leaves.push_bit(true).is_err());
+    }
+
+    #[test]
+    fn test_fill_ranges() {
+        let mut ranges = vec![(0, 2), (3, 5), (5, u32::MAX)];
+        fill_ranges(&mut ranges);
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[3], (u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn test_path_joins() {
+        let parts = vec![("L".to_string(), vec![1]), ("R".to_string(), vec![2])];
+        assert_eq!(
+            path_joins(parts).unwrap(),
+            vec![PathJoin::Left(vec![1]), PathJoin::Right(vec![2])]
+        );
+        assert!(path_joins(vec![("X".to_string(), vec![])]).is_err());
+    }
+}
@@ -1,8 +1,22 @@
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read};
+
+#[cfg(feature = "std")]
+use flate2::read::GzDecoder;
+
 use crate::error::Error;
-use crate::hash::{Digest, HashFold};
+use crate::hash::{Digest, HashFold, Rfc6962Fold};
+#[cfg(feature = "std")]
+use crate::input::process_block;
 use crate::input::BitSink;
-use crate::path::{Path, PathTracker};
-use crate::tree::{TreeFold, TreeFolder};
+use crate::kary::{KTreeFold, KTreeFolder};
+use crate::path::{LeafPath, Path, PathTracker};
+use crate::tree::{FinishResult, NodeVisitor, TreeFold, TreeFolder, TreeFrontier};
 
 #[inline]
 pub fn make_range(left: u32, right: u32) -> [u8; 8] {
@@ -11,6 +25,35 @@ pub fn make_range(left: u32, right: u32) -> [u8; 8] {
     range
 }
 
+pub fn verify_range_path<H: Digest>(
+    index: u32,
+    range: (u32, u32),
+    path: &Path<Vec<u8>>,
+    root: &[u8],
+) -> bool {
+    let (left, right) = range;
+    if index <= left || index >= right {
+        return false;
+    }
+    let mut hasher = HashFold::<H, [u8; 8]>::new();
+    match hasher.input(&make_range(left, right)) {
+        Ok(leaf) if leaf == path.leaf => (),
+        _ => return false,
+    }
+    path.verify(&root.to_vec(), |l, r| hasher.fold(&l, &r).unwrap())
+}
+
+/// Verify a range path, also checking that its directions match the
+/// position of the leaf.
+pub fn verify_range_leaf_path<H: Digest>(
+    index: u32,
+    range: (u32, u32),
+    path: &LeafPath<Vec<u8>>,
+    root: &[u8],
+) -> bool {
+    path.check_directions() && verify_range_path::<H>(index, range, &path.path, root)
+}
+
 pub fn range_hasher<H: Digest>() -> RangeTreeFolder<HashFold<H, [u8; 8]>> {
     RangeTreeFolder::new(HashFold::<H, [u8; 8]>::new())
 }
@@ -19,14 +62,35 @@ pub fn range_path_hasher<H: Digest>(find_index: u32) -> RangePathTracker<HashFol
     RangePathTracker::new(HashFold::<H, [u8; 8]>::new(), find_index)
 }
 
+/// A range hasher using the RFC 6962 domain separation, for trees that are
+/// not filled.
+pub fn ct_range_hasher<H: Digest>() -> RangeTreeFolder<Rfc6962Fold<H, [u8; 8]>> {
+    RangeTreeFolder::new(Rfc6962Fold::<H, [u8; 8]>::new())
+}
+
+pub fn ct_range_path_hasher<H: Digest>(
+    find_index: u32,
+) -> RangePathTracker<Rfc6962Fold<H, [u8; 8]>> {
+    RangePathTracker::new(Rfc6962Fold::<H, [u8; 8]>::new(), find_index)
+}
+
+pub type RangePathResult<T> = (Option<(u32, u32)>, Option<Path<T>>, Option<T>);
+
+pub type RangeLeafPathResult<T> = (Option<(u32, u32)>, Option<LeafPath<T>>, Option<T>);
+
 pub trait RangeTarget {
     type Error;
 
     fn push_range(&mut self, left: u32, right: u32) -> Result<(), Self::Error>;
+
+    /// The number of leaves produced so far, if known.
+    fn leaf_count(&self) -> Option<usize> {
+        None
+    }
 }
 
-pub struct RangeTreeFolder<F: TreeFold<Leaf = [u8; 8]>> {
-    pub folder: TreeFolder<F>,
+pub struct RangeTreeFolder<F: TreeFold<Leaf = [u8; 8]>, V = ()> {
+    pub folder: TreeFolder<F, V>,
 }
 
 impl<T: TreeFold<Leaf = [u8; 8]>> RangeTreeFolder<T> {
@@ -36,6 +100,81 @@ impl<T: TreeFold<Leaf = [u8; 8]>> RangeTreeFolder<T> {
         }
     }
 
+    pub fn resume(base: T, frontier: TreeFrontier<T::Target>) -> Result<Self, Error> {
+        Ok(Self {
+            folder: TreeFolder::resume(base, frontier)?,
+        })
+    }
+}
+
+impl<T: TreeFold<Leaf = [u8; 8]>, V: NodeVisitor<T::Target>> RangeTreeFolder<T, V> {
+    pub fn with_visitor(base: T, visitor: V) -> Self {
+        Self {
+            folder: TreeFolder::with_visitor(base, visitor),
+        }
+    }
+
+    pub fn fill(&mut self) -> usize {
+        self.folder.fill(make_range(u32::MAX, u32::MAX)).unwrap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.folder.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
+    pub fn result(self) -> Option<T::Target> {
+        let (result, _) = self.folder.result().unwrap();
+        result
+    }
+
+    pub fn complete(self) -> (Option<T::Target>, T) {
+        self.folder.result().unwrap()
+    }
+
+    pub fn finish(self) -> FinishResult<T, V> {
+        self.folder.finish().unwrap()
+    }
+
+    pub fn frontier(&self) -> TreeFrontier<T::Target> {
+        self.folder.frontier()
+    }
+
+    pub fn update_base<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut T),
+    {
+        self.folder.update_base(f)
+    }
+}
+
+impl<F: TreeFold<Leaf = [u8; 8]>, V: NodeVisitor<F::Target>> RangeTarget for RangeTreeFolder<F, V> {
+    type Error = F::Error;
+
+    fn push_range(&mut self, left: u32, right: u32) -> Result<(), Self::Error> {
+        let range = make_range(left, right);
+        self.folder.push(&range)
+    }
+
+    fn leaf_count(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+pub struct RangeKTreeFolder<F: KTreeFold<Leaf = [u8; 8]>> {
+    pub folder: KTreeFolder<F>,
+}
+
+impl<T: KTreeFold<Leaf = [u8; 8]>> RangeKTreeFolder<T> {
+    pub fn new(base: T, arity: usize) -> Self {
+        Self {
+            folder: KTreeFolder::new(base, arity),
+        }
+    }
+
     pub fn fill(&mut self) -> usize {
         self.folder.fill(make_range(u32::MAX, u32::MAX)).unwrap()
     }
@@ -44,6 +183,10 @@ impl<T: TreeFold<Leaf = [u8; 8]>> RangeTreeFolder<T> {
         self.folder.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
     pub fn result(self) -> Option<T::Target> {
         let (result, _) = self.folder.result().unwrap();
         result
@@ -61,13 +204,17 @@ impl<T: TreeFold<Leaf = [u8; 8]>> RangeTreeFolder<T> {
     }
 }
 
-impl<F: TreeFold<Leaf = [u8; 8]>> RangeTarget for RangeTreeFolder<F> {
+impl<F: KTreeFold<Leaf = [u8; 8]>> RangeTarget for RangeKTreeFolder<F> {
     type Error = F::Error;
 
     fn push_range(&mut self, left: u32, right: u32) -> Result<(), Self::Error> {
         let range = make_range(left, right);
         self.folder.push(&range)
     }
+
+    fn leaf_count(&self) -> Option<usize> {
+        Some(self.len())
+    }
 }
 
 pub struct RangePathTracker<T: TreeFold<Leaf = [u8; 8]>> {
@@ -93,16 +240,20 @@ impl<T: TreeFold<Leaf = [u8; 8]>> RangePathTracker<T> {
         self.folder.len()
     }
 
-    pub fn result(
-        self,
-    ) -> (
-        Option<(u32, u32)>,
-        Option<Path<T::Target>>,
-        Option<T::Target>,
-    ) {
+    pub fn is_empty(&self) -> bool {
+        self.folder.is_empty()
+    }
+
+    pub fn result(self) -> RangePathResult<T::Target> {
         let (result, tracker) = self.folder.complete();
         (self.range, tracker.path_result(), result)
     }
+
+    pub fn leaf_result(self) -> RangeLeafPathResult<T::Target> {
+        let leaf_count = self.folder.len();
+        let (result, tracker) = self.folder.complete();
+        (self.range, tracker.leaf_path_result(leaf_count), result)
+    }
 }
 
 impl<F: TreeFold<Leaf = [u8; 8]>> RangeTarget for RangePathTracker<F> {
@@ -115,6 +266,10 @@ impl<F: TreeFold<Leaf = [u8; 8]>> RangeTarget for RangePathTracker<F> {
         }
         self.folder.push_range(left, right)
     }
+
+    fn leaf_count(&self) -> Option<usize> {
+        Some(self.len())
+    }
 }
 
 pub struct RangeParser<T: RangeTarget> {
@@ -133,6 +288,110 @@ impl<T: RangeTarget> RangeParser<T> {
             target,
         }
     }
+
+    /// Continue parsing from a saved state, with a target holding the
+    /// ranges already produced.
+    pub fn resume(target: T, state: RangeParserState) -> Result<Self, Error> {
+        let RangeParserState {
+            left,
+            in_rev,
+            bit_idx,
+        } = state;
+        if bit_idx == 0 || left >= bit_idx || (in_rev && left != bit_idx - 1) {
+            return Err(Error::InvalidInput(format!(
+                "invalid parser state: {:?}",
+                state
+            )));
+        }
+        Ok(Self {
+            left,
+            in_rev,
+            bit_idx,
+            target,
+        })
+    }
+
+    pub fn state(&self) -> RangeParserState {
+        RangeParserState {
+            left: self.left,
+            in_rev: self.in_rev,
+            bit_idx: self.bit_idx,
+        }
+    }
+
+    /// The number of bits processed.
+    pub fn position(&self) -> u32 {
+        self.bit_idx - 1
+    }
+}
+
+/// The position of a `RangeParser` within a bitmap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RangeParserState {
+    pub left: u32,
+    pub in_rev: bool,
+    pub bit_idx: u32,
+}
+
+/// A saved `RangeParser` feeding a range hasher, from which hashing may be
+/// resumed with the remainder of the bitmap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeCheckpoint {
+    pub state: RangeParserState,
+    pub frontier: TreeFrontier<Vec<u8>>,
+}
+
+impl RangeCheckpoint {
+    /// Encode as `left` and `bit_idx` (big-endian u32), `in_rev` as a byte,
+    /// and the encoded frontier.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.state.left.to_be_bytes());
+        out.extend_from_slice(&self.state.bit_idx.to_be_bytes());
+        out.push(self.state.in_rev as u8);
+        out.extend(self.frontier.to_bytes());
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 9 || data[8] > 1 {
+            return Err(Error::InvalidInput("invalid checkpoint".to_string()));
+        }
+        let state = RangeParserState {
+            left: u32::from_be_bytes(data[..4].try_into().unwrap()),
+            bit_idx: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            in_rev: data[8] != 0,
+        };
+        let frontier = TreeFrontier::from_bytes(&data[9..])?;
+        Ok(Self { state, frontier })
+    }
+}
+
+impl<F> RangeParser<RangeTreeFolder<F>>
+where
+    F: TreeFold<Leaf = [u8; 8], Target = Vec<u8>>,
+{
+    pub fn checkpoint(&self) -> RangeCheckpoint {
+        RangeCheckpoint {
+            state: self.state(),
+            frontier: self.target.frontier(),
+        }
+    }
+
+    /// Resume from a checkpoint, after which the bitmap is fed from byte
+    /// `position() / 8`. Checkpoints not taken on a byte boundary are
+    /// rejected, as they cannot be resumed from a byte offset.
+    pub fn from_checkpoint(base: F, checkpoint: RangeCheckpoint) -> Result<Self, Error> {
+        let position = checkpoint.state.bit_idx.wrapping_sub(1);
+        if !position.is_multiple_of(8) {
+            return Err(Error::InvalidInput(format!(
+                "checkpoint position not on a byte boundary: {}",
+                position
+            )));
+        }
+        let target = RangeTreeFolder::resume(base, checkpoint.frontier)?;
+        Self::resume(target, checkpoint.state)
+    }
 }
 
 impl<T: RangeTarget> BitSink for RangeParser<T>
@@ -147,7 +406,7 @@ where
                 self.target.push_range(self.left, self.bit_idx)?;
                 self.in_rev = true;
             }
-            self.left = self.bit_idx;
+            self.left = self.bit_idx + count - 1;
         } else {
             self.in_rev = false;
         }
@@ -159,6 +418,90 @@ where
         self.target.push_range(self.left, u32::MAX)?;
         Ok(self.target)
     }
+
+    fn leaf_count(&self) -> Option<usize> {
+        self.target.leaf_count()
+    }
+}
+
+#[derive(Default)]
+pub struct RangeQueue {
+    pub ranges: VecDeque<(u32, u32)>,
+}
+
+impl RangeTarget for RangeQueue {
+    type Error = core::convert::Infallible;
+
+    fn push_range(&mut self, left: u32, right: u32) -> Result<(), Self::Error> {
+        self.ranges.push_back((left, right));
+        Ok(())
+    }
+}
+
+/// Iterate the non-revoked ranges of a bitmap, reading from the source
+/// only as more ranges are needed. Iteration ends after the first error.
+#[cfg(feature = "std")]
+pub struct RangeIter<R: Read> {
+    source: R,
+    buf: Vec<u8>,
+    parser: Option<RangeParser<RangeQueue>>,
+    remain: VecDeque<(u32, u32)>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> RangeIter<R> {
+    /// Read an uncompressed bitmap.
+    pub fn new(source: R) -> Self {
+        Self {
+            source,
+            buf: vec![0u8; 1024],
+            parser: Some(RangeParser::new(RangeQueue::default())),
+            remain: VecDeque::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for RangeIter<R> {
+    type Item = Result<(u32, u32), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let parser = match self.parser.as_mut() {
+                Some(parser) => parser,
+                None => return self.remain.pop_front().map(Ok),
+            };
+            if let Some(range) = parser.target.ranges.pop_front() {
+                return Some(Ok(range));
+            }
+            let result = match self.source.read(self.buf.as_mut_slice()) {
+                Ok(0) => {
+                    let parser = self.parser.take().unwrap();
+                    parser.complete().map(|queue| self.remain = queue.ranges)
+                }
+                Ok(sz) => process_block(parser, &self.buf[..sz]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => Ok(()),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                self.parser.take();
+                self.remain.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Iterate the non-revoked ranges of a bitmap in the gzip format.
+#[cfg(feature = "std")]
+pub fn iter_zipped_ranges<R: Read>(source: R) -> RangeIter<GzDecoder<R>> {
+    RangeIter::new(GzDecoder::new(source))
+}
+
+/// Iterate the non-revoked ranges of an in-memory bitmap.
+#[cfg(feature = "std")]
+pub fn iter_bitmap_ranges(bitmap: &[u8]) -> RangeIter<&[u8]> {
+    RangeIter::new(bitmap)
 }
 
 #[cfg(test)]
@@ -194,4 +537,284 @@ mod test {
         let collect = parser.complete().unwrap();
         assert_eq!(collect.result, vec![(0, 1), (1, 4), (4, u32::MAX)]);
     }
+
+    #[test]
+    fn test_range_runs() {
+        let mut parser = RangeParser::new(RangeCollect::new());
+        parser.process_bits(false, 2).unwrap();
+        parser.process_bits(true, 64).unwrap();
+        parser.process_bits(false, 64).unwrap();
+        parser.process_bits(true, 1).unwrap();
+        let collect = parser.complete().unwrap();
+        assert_eq!(collect.result, vec![(0, 3), (66, 131), (131, u32::MAX)]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_range_iter() {
+        use crate::input::process_bitmap;
+        use crate::registry::RevocationRegistry;
+        use sha2::Sha256;
+        use std::io;
+
+        let mut registry = RevocationRegistry::new(4096);
+        for index in &[1, 2, 64, 65, 66, 200, 1000, 4096] {
+            registry.revoke(*index).unwrap();
+        }
+        let ranges: Vec<(u32, u32)> = iter_bitmap_ranges(registry.as_bitmap())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ranges, registry.ranges().collect::<Vec<_>>());
+
+        let zipped = registry.save(vec![]).unwrap();
+        let zipped_ranges: Vec<(u32, u32)> = iter_zipped_ranges(zipped.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(zipped_ranges, ranges);
+
+        // feed the leaves directly to a tree
+        let mut folder = TreeFolder::new(HashFold::<Sha256, [u8; 8]>::new());
+        folder
+            .extend(iter_bitmap_ranges(registry.as_bitmap()).map(|r| {
+                let (left, right) = r.unwrap();
+                make_range(left, right)
+            }))
+            .unwrap();
+        let (root, _) = folder.result().unwrap();
+        let hasher = process_bitmap(
+            registry.as_bitmap(),
+            RangeParser::new(range_hasher::<Sha256>()),
+        );
+        assert_eq!(root, hasher.unwrap().result());
+
+        // ranges that differ between two registries
+        let mut other = registry.clone();
+        other.revoke(500).unwrap();
+        let diff = iter_bitmap_ranges(registry.as_bitmap())
+            .zip(iter_bitmap_ranges(other.as_bitmap()))
+            .position(|(a, b)| a.unwrap() != b.unwrap());
+        assert_eq!(diff, Some(3));
+
+        struct FailRead;
+        impl io::Read for FailRead {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("failed"))
+            }
+        }
+        let mut iter = RangeIter::new(FailRead);
+        assert!(matches!(iter.next(), Some(Err(Error::Io(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_revoked_block_not_covered() {
+        // indices 65 to 128 form a fully revoked 64-bit block, which was
+        // previously given the leaf (65, 129) covering indices 66 to 128
+        let mut bitmap = vec![0u8; 24];
+        bitmap[8..16].copy_from_slice(&[0xff; 8]);
+        let collect =
+            crate::input::process_bitmap(&bitmap, RangeParser::new(RangeCollect::new())).unwrap();
+        assert_eq!(collect.result, vec![(0, 65), (128, u32::MAX)]);
+        for index in 65..=128 {
+            assert!(collect
+                .result
+                .iter()
+                .all(|(left, right)| !(*left < index && index < *right)));
+        }
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        use crate::input::{process_bitmap, process_block};
+        use sha2::Sha256;
+
+        let bitmap: Vec<u8> = (0..64u32).map(|n| (n * n * 37 % 251) as u8).collect();
+        let expect = process_bitmap(&bitmap, RangeParser::new(range_hasher::<Sha256>()))
+            .unwrap()
+            .result();
+        for split in &[0, 1, 7, 8, 13, 40, 64] {
+            let mut parser = RangeParser::new(range_hasher::<Sha256>());
+            process_block(&mut parser, &bitmap[..*split]).unwrap();
+            assert_eq!(parser.position(), *split as u32 * 8);
+            let saved = parser.checkpoint().to_bytes();
+
+            let checkpoint = RangeCheckpoint::from_bytes(&saved).unwrap();
+            let parser =
+                RangeParser::from_checkpoint(HashFold::<Sha256, [u8; 8]>::new(), checkpoint)
+                    .unwrap();
+            let resumed = process_bitmap(&bitmap[*split..], parser).unwrap();
+            assert_eq!(resumed.result(), expect, "{}", split);
+        }
+
+        assert!(RangeCheckpoint::from_bytes(&[0; 8]).is_err());
+        let state = RangeParserState {
+            left: 5,
+            in_rev: true,
+            bit_idx: 5,
+        };
+        assert!(RangeParser::resume(RangeQueue::default(), state).is_err());
+
+        // a checkpoint within a byte cannot be resumed from a byte offset
+        let mut parser = RangeParser::new(range_hasher::<Sha256>());
+        parser.process_bits(false, 3).unwrap();
+        parser.process_bits(true, 1).unwrap();
+        let checkpoint = parser.checkpoint();
+        assert!(matches!(
+            RangeParser::from_checkpoint(HashFold::<Sha256, [u8; 8]>::new(), checkpoint),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_resume_zipped() {
+        use crate::input::{process_block, process_zipped_reader, process_zipped_reader_from};
+        use crate::registry::RevocationRegistry;
+        use sha2::Sha256;
+
+        let mut registry = RevocationRegistry::new(1 << 14);
+        for index in (1..1 << 14).step_by(97) {
+            registry.revoke(index).unwrap();
+        }
+        let zipped = registry.save(vec![]).unwrap();
+        let expect = process_zipped_reader(
+            zipped.as_slice(),
+            RangeParser::new(range_hasher::<Sha256>()),
+        )
+        .unwrap()
+        .result();
+
+        // checkpoint part way through, then resume from the compressed file
+        let mut parser = RangeParser::new(range_hasher::<Sha256>());
+        process_block(&mut parser, &registry.as_bitmap()[..1500]).unwrap();
+        let skip = parser.position() as u64 / 8;
+        let checkpoint = parser.checkpoint();
+        let parser =
+            RangeParser::from_checkpoint(HashFold::<Sha256, [u8; 8]>::new(), checkpoint).unwrap();
+        let resumed = process_zipped_reader_from(zipped.as_slice(), skip, parser).unwrap();
+        assert_eq!(resumed.result(), expect);
+    }
+
+    #[test]
+    fn test_verify_range_path() {
+        use sha2::Sha256;
+
+        let bits = &[false, true, false, false, true, true, false];
+        let mut parser = RangeParser::new(range_path_hasher::<Sha256>(3));
+        for bit in bits {
+            parser.process_bits(*bit, 1).unwrap();
+        }
+        let mut tracker = parser.complete().unwrap();
+        tracker.fill();
+        let (range, path, root) = tracker.result();
+        let (range, path, root) = (range.unwrap(), path.unwrap(), root.unwrap());
+        assert_eq!(range, (2, 5));
+        assert!(verify_range_path::<Sha256>(3, range, &path, &root));
+        assert!(verify_range_path::<Sha256>(4, range, &path, &root));
+        assert!(!verify_range_path::<Sha256>(5, range, &path, &root));
+        assert!(!verify_range_path::<Sha256>(3, (1, 5), &path, &root));
+    }
+
+    #[test]
+    fn test_unfilled_path() {
+        use crate::{find_bitmap_path_unfilled, hash_bitmap};
+        use sha2::Sha256;
+
+        // six ranges, as peaks of 4 and 2 leaves
+        let bitmap = &[0x55, 0x0f];
+        let unfilled = hash_bitmap::<Sha256>(bitmap, false).unwrap();
+        let filled = hash_bitmap::<Sha256>(bitmap, true).unwrap();
+        assert_eq!((unfilled.leaf_count, filled.filled_count), (6, 8));
+        assert_ne!(unfilled.root, filled.root);
+
+        let mut found = 0;
+        for index in 1..20 {
+            let (range, path, result) = find_bitmap_path_unfilled::<Sha256>(bitmap, index).unwrap();
+            assert_eq!(result.filled_count, 6);
+            assert_eq!(result.root, unfilled.root);
+            let (range, path) = match (range, path) {
+                (Some(range), Some(path)) => (range, path),
+                _ => continue,
+            };
+            let root = result.root.unwrap();
+            assert!(verify_range_path::<Sha256>(index, range, &path, &root));
+            // the first four leaves are two levels below a right join
+            let depth = if range.0 < 8 { 3 } else { 2 };
+            assert_eq!(path.join.len(), depth, "{}", index);
+            found += 1;
+        }
+        assert_eq!(found, 4 + 4 + 3);
+    }
+
+    #[test]
+    fn test_ct_consistency() {
+        use crate::consistency::{verify_ct_consistency, ConsistencyTracker};
+        use sha2::Sha256;
+
+        let ranges = [(0, 3), (3, 7), (9, 10), (10, 12), (15, 20), (20, 31)];
+        let mut old = ct_range_hasher::<Sha256>();
+        for (left, right) in &ranges[..3] {
+            old.push_range(*left, *right).unwrap();
+        }
+        let old_root = old.result().unwrap();
+
+        let mut new = RangeTreeFolder::new(ConsistencyTracker::new(
+            Rfc6962Fold::<Sha256, [u8; 8]>::new(),
+            3,
+            ranges.len(),
+        ));
+        for (left, right) in &ranges {
+            new.push_range(*left, *right).unwrap();
+        }
+        let (new_root, tracker) = new.complete();
+        let new_root = new_root.unwrap();
+        let proof = tracker.proof_result().unwrap();
+        assert_eq!(proof.nodes.len(), 4);
+        assert!(verify_ct_consistency::<Sha256>(
+            &proof, &old_root, &new_root
+        ));
+        assert!(!verify_ct_consistency::<Sha256>(
+            &proof, &new_root, &old_root
+        ));
+
+        // inclusion proofs in the same tree are bound to the leaf position
+        let mut tracker = ct_range_path_hasher::<Sha256>(11);
+        for (left, right) in &ranges {
+            tracker.push_range(*left, *right).unwrap();
+        }
+        let (range, path, root) = tracker.leaf_result();
+        let path = path.unwrap();
+        assert_eq!((range, path.index), (Some((10, 12)), 3));
+        assert_eq!(root.unwrap(), new_root);
+        let mut hasher = Rfc6962Fold::<Sha256, [u8; 8]>::new();
+        assert!(path.verify(&new_root, |l, r| hasher.fold(&l, &r).unwrap()));
+    }
+
+    #[test]
+    fn test_verify_range_leaf_path() {
+        use sha2::Sha256;
+
+        let bits = &[false, true, false, false, true, true, false];
+        for fill in &[false, true] {
+            let mut parser = RangeParser::new(range_path_hasher::<Sha256>(3));
+            for bit in bits {
+                parser.process_bits(*bit, 1).unwrap();
+            }
+            let mut tracker = parser.complete().unwrap();
+            if *fill {
+                tracker.fill();
+            }
+            let (range, path, root) = tracker.leaf_result();
+            let (range, path, root) = (range.unwrap(), path.unwrap(), root.unwrap());
+            assert_eq!(
+                (path.index, path.leaf_count),
+                (1, if *fill { 4 } else { 3 })
+            );
+            assert!(verify_range_leaf_path::<Sha256>(3, range, &path, &root));
+
+            let mut moved = path.clone();
+            moved.index = 0;
+            assert!(!verify_range_leaf_path::<Sha256>(3, range, &moved, &root));
+        }
+    }
 }
@@ -0,0 +1,324 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "std")]
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::error::Error;
+use crate::hash::Digest;
+use crate::input::process_bitmap;
+use crate::range::{range_hasher, range_path_hasher, RangeParser, RangePathResult};
+
+/// An in-memory revocation bitmap, where the bit for registry index `i` is
+/// bit `i - 1` counting from the most significant bit of the first byte.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RevocationRegistry {
+    bits: Vec<u8>,
+    capacity: u32,
+}
+
+impl RevocationRegistry {
+    /// Create a registry for the indices `1..=capacity`, none revoked. Index
+    /// `u32::MAX` is reserved for the end of the tree, so a larger capacity
+    /// is reduced to `u32::MAX - 1`.
+    pub fn new(capacity: u32) -> Self {
+        let capacity = capacity.min(u32::MAX - 1);
+        Self {
+            bits: vec![0u8; (capacity as usize).div_ceil(8)],
+            capacity,
+        }
+    }
+
+    /// Create a registry from a bitmap, with a capacity of every bit in the
+    /// bitmap.
+    pub fn from_bitmap(bitmap: Vec<u8>) -> Result<Self, Error> {
+        if bitmap.len() as u64 * 8 >= u32::MAX as u64 {
+            return Err(Error::InvalidInput(format!(
+                "bitmap too large: {} bytes",
+                bitmap.len()
+            )));
+        }
+        let capacity = bitmap.len() as u32 * 8;
+        Ok(Self {
+            bits: bitmap,
+            capacity,
+        })
+    }
+
+    pub fn as_bitmap(&self) -> &[u8] {
+        &self.bits
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn revoked_count(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    fn position(&self, index: u32) -> Result<(usize, u8), Error> {
+        if index == 0 || index > self.capacity {
+            return Err(Error::InvalidInput(format!(
+                "index out of range: {}",
+                index
+            )));
+        }
+        let bit = index - 1;
+        Ok(((bit / 8) as usize, 0x80 >> (bit % 8)))
+    }
+
+    /// Mark an index as revoked, returning `false` if it already was.
+    pub fn revoke(&mut self, index: u32) -> Result<bool, Error> {
+        let (byte, mask) = self.position(index)?;
+        let prev = self.bits[byte] & mask != 0;
+        self.bits[byte] |= mask;
+        Ok(!prev)
+    }
+
+    /// Clear the revocation of an index, returning `false` if it was not
+    /// revoked.
+    pub fn unrevoke(&mut self, index: u32) -> Result<bool, Error> {
+        let (byte, mask) = self.position(index)?;
+        let prev = self.bits[byte] & mask != 0;
+        self.bits[byte] &= !mask;
+        Ok(prev)
+    }
+
+    pub fn is_revoked(&self, index: u32) -> bool {
+        match self.position(index) {
+            Ok((byte, mask)) => self.bits[byte] & mask != 0,
+            Err(_) => false,
+        }
+    }
+
+    /// Iterate the non-revoked ranges, as produced by a `RangeParser`.
+    pub fn ranges(&self) -> RegistryRanges<'_> {
+        RegistryRanges {
+            registry: self,
+            left: 0,
+            next: 1,
+            done: false,
+        }
+    }
+
+    /// The first index from `start` with the given revocation status.
+    fn find(&self, start: u32, revoked: bool) -> Option<u32> {
+        let skip = if revoked { 0x00 } else { 0xff };
+        // a u64 cursor, as skipping a byte may pass u32::MAX
+        let mut index = start as u64;
+        while index <= self.capacity as u64 {
+            let bit = index - 1;
+            let byte = self.bits[(bit / 8) as usize];
+            if bit.is_multiple_of(8) && byte == skip {
+                index += 8;
+                continue;
+            }
+            if (byte & (0x80 >> (bit % 8)) != 0) == revoked {
+                return Some(index as u32);
+            }
+            index += 1;
+        }
+        None
+    }
+
+    /// Compute the root of the filled range tree.
+    pub fn root<H: Digest>(&self) -> Option<Vec<u8>> {
+        let mut hasher = process_bitmap(&self.bits, RangeParser::new(range_hasher::<H>())).ok()?;
+        hasher.fill();
+        hasher.result()
+    }
+
+    /// Find the non-revocation path for an index in the filled range tree.
+    pub fn find_path<H: Digest>(&self, index: u32) -> RangePathResult<Vec<u8>> {
+        let parser = RangeParser::new(range_path_hasher::<H>(index));
+        let mut tracker = process_bitmap(&self.bits, parser).unwrap();
+        tracker.fill();
+        tracker.result()
+    }
+
+    /// Read a registry from the gzip bitmap format. The format holds whole
+    /// bytes only, so the capacity is that of the bitmap: a registry saved
+    /// with a capacity which is not a multiple of 8 is loaded with its
+    /// capacity rounded up to a whole byte.
+    #[cfg(feature = "std")]
+    pub fn load<R: Read>(source: R) -> Result<Self, Error> {
+        let mut bitmap = vec![];
+        GzDecoder::new(source).read_to_end(&mut bitmap)?;
+        Self::from_bitmap(bitmap)
+    }
+
+    /// Write the registry in the gzip bitmap format, without its capacity.
+    #[cfg(feature = "std")]
+    pub fn save<W: Write>(&self, target: W) -> Result<W, Error> {
+        let mut encoder = GzEncoder::new(target, Compression::default());
+        encoder.write_all(&self.bits)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+pub struct RegistryRanges<'r> {
+    registry: &'r RevocationRegistry,
+    left: u32,
+    next: u32,
+    done: bool,
+}
+
+impl Iterator for RegistryRanges<'_> {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.registry.find(self.next, true) {
+            Some(revoked) => {
+                let range = (self.left, revoked);
+                let end = self
+                    .registry
+                    .find(revoked, false)
+                    .unwrap_or(self.registry.capacity + 1);
+                self.left = end - 1;
+                self.next = end;
+                Some(range)
+            }
+            None => {
+                self.done = true;
+                Some((self.left, u32::MAX))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::RangeQueue;
+    use sha2::Sha256;
+
+    fn parsed_ranges(registry: &RevocationRegistry) -> Vec<(u32, u32)> {
+        let parser = RangeParser::new(RangeQueue::default());
+        process_bitmap(registry.as_bitmap(), parser)
+            .unwrap()
+            .ranges
+            .into()
+    }
+
+    #[test]
+    fn test_revoke() {
+        let mut registry = RevocationRegistry::new(20);
+        assert_eq!(registry.capacity(), 20);
+        assert_eq!(registry.as_bitmap().len(), 3);
+        assert!(registry.revoke(1).unwrap());
+        assert!(!registry.revoke(1).unwrap());
+        assert!(registry.revoke(20).unwrap());
+        assert!(registry.is_revoked(1));
+        assert!(!registry.is_revoked(2));
+        assert!(!registry.is_revoked(21));
+        assert!(matches!(registry.revoke(0), Err(Error::InvalidInput(_))));
+        assert!(matches!(registry.revoke(21), Err(Error::InvalidInput(_))));
+        assert_eq!(registry.revoked_count(), 2);
+        assert!(registry.unrevoke(1).unwrap());
+        assert!(!registry.unrevoke(1).unwrap());
+        assert_eq!(registry.revoked_count(), 1);
+        assert_eq!(registry.as_bitmap(), &[0, 0, 0x10]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut registry = RevocationRegistry::new(64);
+        assert_eq!(
+            registry.ranges().collect::<Vec<_>>(),
+            parsed_ranges(&registry)
+        );
+        for index in &[1, 2, 3, 9, 17, 18, 30, 64] {
+            registry.revoke(*index).unwrap();
+        }
+        assert_eq!(
+            registry.ranges().collect::<Vec<_>>(),
+            vec![(0, 1), (3, 9), (9, 17), (18, 30), (30, 64), (64, u32::MAX)]
+        );
+        assert_eq!(
+            registry.ranges().collect::<Vec<_>>(),
+            parsed_ranges(&registry)
+        );
+
+        let full = RevocationRegistry::from_bitmap(vec![0xff; 4]).unwrap();
+        assert_eq!(
+            full.ranges().collect::<Vec<_>>(),
+            vec![(0, 1), (32, u32::MAX)]
+        );
+    }
+
+    #[test]
+    fn test_root() {
+        let mut registry = RevocationRegistry::new(256);
+        for index in &[5, 6, 100, 255] {
+            registry.revoke(*index).unwrap();
+        }
+        let root = registry.root::<Sha256>().unwrap();
+        let (range, path, path_root) = registry.find_path::<Sha256>(50);
+        assert_eq!(range, Some((6, 100)));
+        assert_eq!(path_root.as_ref(), Some(&root));
+        assert!(crate::range::verify_range_path::<Sha256>(
+            50,
+            range.unwrap(),
+            &path.unwrap(),
+            &root
+        ));
+
+        registry.unrevoke(100).unwrap();
+        assert_ne!(registry.root::<Sha256>().unwrap(), root);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_load() {
+        let mut registry = RevocationRegistry::new(1024);
+        for index in &[1, 64, 65, 500, 1024] {
+            registry.revoke(*index).unwrap();
+        }
+        let zipped = registry.save(vec![]).unwrap();
+        let parser = RangeParser::new(RangeQueue::default());
+        let collect = crate::input::process_zipped_reader(zipped.as_slice(), parser).unwrap();
+        assert_eq!(collect.ranges, registry.ranges().collect::<Vec<_>>());
+
+        let loaded = RevocationRegistry::load(zipped.as_slice()).unwrap();
+        assert_eq!(loaded, registry);
+        assert_eq!(loaded.root::<Sha256>(), registry.root::<Sha256>());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_load_partial_byte() {
+        let mut registry = RevocationRegistry::new(20);
+        registry.revoke(3).unwrap();
+        registry.revoke(20).unwrap();
+        let zipped = registry.save(vec![]).unwrap();
+        let loaded = RevocationRegistry::load(zipped.as_slice()).unwrap();
+        // the capacity is rounded up to a whole byte
+        assert_eq!(loaded.capacity(), 24);
+        assert_ne!(loaded, registry);
+        assert_eq!(loaded.as_bitmap(), registry.as_bitmap());
+        assert_eq!(
+            loaded.ranges().collect::<Vec<_>>(),
+            registry.ranges().collect::<Vec<_>>()
+        );
+        assert_eq!(loaded.root::<Sha256>(), registry.root::<Sha256>());
+        assert!(registry.revoke(24).is_err());
+    }
+
+    #[test]
+    fn test_max_capacity() {
+        let mut registry = RevocationRegistry::new(u32::MAX);
+        assert_eq!(registry.capacity(), u32::MAX - 1);
+        // the last whole byte starts at u32::MAX - 6
+        assert_eq!(registry.find(u32::MAX - 6, true), None);
+        registry.revoke(u32::MAX - 1).unwrap();
+        assert_eq!(registry.find(u32::MAX - 6, true), Some(u32::MAX - 1));
+        assert_eq!(registry.find(u32::MAX - 1, false), None);
+    }
+}
@@ -0,0 +1,343 @@
+//! Status lists with more than one bit per entry.
+//!
+//! Each entry holds a status value of 1, 2, 4 or 8 bits. Following the IETF
+//! Token Status List, `0` is valid, `1` is invalid (revoked) and `2` is
+//! suspended. A `StatusFilter` feeds any `BitSink` with only the entries
+//! holding one status treated as non-revoked, so that a `RangeParser` over
+//! it produces no range containing a suspended entry, while a `StatusSplit`
+//! builds one target per status value in a single pass.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use crate::error::Error;
+#[cfg(feature = "std")]
+use crate::input::fold_zipped_blocks;
+use crate::input::BitSink;
+
+pub const STATUS_VALID: u8 = 0;
+pub const STATUS_INVALID: u8 = 1;
+pub const STATUS_SUSPENDED: u8 = 2;
+
+/// The number of bits per status entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusSize {
+    One = 1,
+    Two = 2,
+    Four = 4,
+    Eight = 8,
+}
+
+impl StatusSize {
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            1 => Some(Self::One),
+            2 => Some(Self::Two),
+            4 => Some(Self::Four),
+            8 => Some(Self::Eight),
+            _ => None,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        self as u32
+    }
+
+    /// The number of distinct status values.
+    pub fn values(self) -> usize {
+        1 << self.bits()
+    }
+}
+
+/// The position of the first entry within each byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOrder {
+    /// The first entry is in the most significant bits, as in a W3C
+    /// Bitstring Status List and the single-bit registry format.
+    Msb,
+    /// The first entry is in the least significant bits, as in an IETF
+    /// Token Status List.
+    Lsb,
+}
+
+pub trait StatusSink {
+    type Result;
+
+    fn process_status(&mut self, status: u8, count: u32) -> Result<(), Error>;
+
+    fn complete(self) -> Result<Self::Result, Error>;
+}
+
+fn process_status_block<T>(
+    proc: &mut T,
+    size: StatusSize,
+    order: BitOrder,
+    block: &[u8],
+) -> Result<(), Error>
+where
+    T: StatusSink,
+{
+    let bits = size.bits();
+    let per_byte = 8 / bits;
+    let mask = (size.values() - 1) as u8;
+    for byte in block {
+        if *byte == 0 {
+            proc.process_status(STATUS_VALID, per_byte)?;
+            continue;
+        }
+        for pos in 0..per_byte {
+            let shift = match order {
+                BitOrder::Msb => 8 - bits * (pos + 1),
+                BitOrder::Lsb => bits * pos,
+            };
+            proc.process_status(byte >> shift & mask, 1)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn process_status_bitmap<T>(
+    bitmap: &[u8],
+    size: StatusSize,
+    order: BitOrder,
+    mut proc: T,
+) -> Result<T::Result, Error>
+where
+    T: StatusSink,
+{
+    process_status_block(&mut proc, size, order, bitmap)?;
+    proc.complete()
+}
+
+#[cfg(feature = "std")]
+pub fn process_zipped_status_reader<R, T>(
+    source: R,
+    size: StatusSize,
+    order: BitOrder,
+    proc: T,
+) -> Result<T::Result, Error>
+where
+    R: Read,
+    T: StatusSink,
+{
+    let target = fold_zipped_blocks(source, proc, None, |mut proc, block, _| {
+        process_status_block(&mut proc, size, order, block)?;
+        Result::<_, Error>::Ok(proc)
+    })?;
+    target.complete()
+}
+
+/// Pass entries to a `BitSink`, treating every entry whose status is not
+/// `valid` as revoked.
+pub struct StatusFilter<T: BitSink> {
+    pub valid: u8,
+    pub target: T,
+}
+
+impl<T: BitSink> StatusFilter<T> {
+    pub fn new(target: T) -> Self {
+        Self::with_valid(STATUS_VALID, target)
+    }
+
+    pub fn with_valid(valid: u8, target: T) -> Self {
+        Self { valid, target }
+    }
+}
+
+impl<T: BitSink> StatusSink for StatusFilter<T> {
+    type Result = T::Result;
+
+    fn process_status(&mut self, status: u8, count: u32) -> Result<(), Error> {
+        self.target.process_bits(status != self.valid, count)
+    }
+
+    fn complete(self) -> Result<Self::Result, Error> {
+        self.target.complete()
+    }
+}
+
+/// Pass entries to one `BitSink` per status value, where each target sees
+/// only the entries holding its own status as non-revoked.
+///
+/// Entries past the end of the list are non-revoked only for the `valid`
+/// status, so only its final range extends to `u32::MAX`. The other targets
+/// see every later index up to `u32::MAX - 1` as revoked, so that no index
+/// outside the list can be proven to hold that status. The padding is passed
+/// as a single run, which suits a `RangeParser` but not a `BitmapWriter`.
+pub struct StatusSplit<T: BitSink> {
+    pub valid: u8,
+    pub targets: Vec<T>,
+    len: u32,
+}
+
+impl<T: BitSink> StatusSplit<T> {
+    pub fn new(size: StatusSize, f: impl FnMut(u8) -> T) -> Self {
+        Self::with_valid(STATUS_VALID, size, f)
+    }
+
+    pub fn with_valid(valid: u8, size: StatusSize, mut f: impl FnMut(u8) -> T) -> Self {
+        Self {
+            valid,
+            targets: (0..size.values()).map(|status| f(status as u8)).collect(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: BitSink> StatusSink for StatusSplit<T> {
+    type Result = Vec<T::Result>;
+
+    fn process_status(&mut self, status: u8, count: u32) -> Result<(), Error> {
+        for (idx, target) in self.targets.iter_mut().enumerate() {
+            target.process_bits(idx != status as usize, count)?;
+        }
+        self.len = self.len.saturating_add(count);
+        Ok(())
+    }
+
+    fn complete(self) -> Result<Self::Result, Error> {
+        // registry indices start at 1 and u32::MAX is the end of the tree
+        let padding = (u32::MAX - 1).saturating_sub(self.len);
+        let valid = self.valid as usize;
+        self.targets
+            .into_iter()
+            .enumerate()
+            .map(|(idx, mut target)| {
+                if idx != valid && padding > 0 {
+                    target.process_bits(true, padding)?;
+                }
+                target.complete()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::{RangeParser, RangeQueue};
+    use alloc::vec;
+
+    struct StatusCollect {
+        result: Vec<u8>,
+    }
+
+    impl StatusSink for StatusCollect {
+        type Result = Vec<u8>;
+
+        fn process_status(&mut self, status: u8, count: u32) -> Result<(), Error> {
+            self.result
+                .resize(self.result.len() + count as usize, status);
+            Ok(())
+        }
+
+        fn complete(self) -> Result<Self::Result, Error> {
+            Ok(self.result)
+        }
+    }
+
+    #[test]
+    fn test_status_order() {
+        // the 2-bit example from the IETF Token Status List
+        let bitmap = [0xc9, 0x44, 0xf9];
+        let collect = StatusCollect { result: vec![] };
+        let status =
+            process_status_bitmap(&bitmap, StatusSize::Two, BitOrder::Lsb, collect).unwrap();
+        assert_eq!(status, vec![1, 2, 0, 3, 0, 1, 0, 1, 1, 2, 3, 3]);
+
+        let collect = StatusCollect { result: vec![] };
+        let status =
+            process_status_bitmap(&[0x1f, 0x00], StatusSize::Four, BitOrder::Msb, collect).unwrap();
+        assert_eq!(status, vec![1, 15, 0, 0]);
+        assert_eq!(StatusSize::from_bits(3), None);
+    }
+
+    #[test]
+    fn test_suspended() {
+        // entries 1 to 8 are valid, invalid, valid, suspended, valid, valid,
+        // suspended, valid
+        let bitmap = [0b1000_0100, 0b0010_0000];
+        let parser = RangeParser::new(RangeQueue::default());
+        let filter = StatusFilter::new(parser);
+        let collect =
+            process_status_bitmap(&bitmap, StatusSize::Two, BitOrder::Lsb, filter).unwrap();
+        assert_eq!(collect.ranges, vec![(0, 2), (2, 4), (4, 7), (7, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_split() {
+        let bitmap = [0b1000_0100, 0b0010_0000];
+        let split = StatusSplit::new(StatusSize::Two, |_| RangeParser::new(RangeQueue::default()));
+        let results =
+            process_status_bitmap(&bitmap, StatusSize::Two, BitOrder::Lsb, split).unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[STATUS_VALID as usize].ranges,
+            vec![(0, 2), (2, 4), (4, 7), (7, u32::MAX)]
+        );
+        // entries past the list hold no status other than valid
+        assert_eq!(
+            results[STATUS_INVALID as usize].ranges,
+            vec![(0, 1), (1, 3), (u32::MAX - 1, u32::MAX)]
+        );
+        assert_eq!(
+            results[STATUS_SUSPENDED as usize].ranges,
+            vec![(0, 1), (3, 5), (6, 8), (u32::MAX - 1, u32::MAX)]
+        );
+        assert_eq!(results[3].ranges, vec![(0, 1), (u32::MAX - 1, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_split_padding() {
+        // the last entry is suspended, and its range ends with the list
+        let split = StatusSplit::new(StatusSize::Two, |_| RangeParser::new(RangeQueue::default()));
+        let results =
+            process_status_bitmap(&[0b1000_0000], StatusSize::Two, BitOrder::Lsb, split).unwrap();
+        assert_eq!(
+            results[STATUS_VALID as usize].ranges,
+            vec![(0, 4), (4, u32::MAX)]
+        );
+        assert_eq!(
+            results[STATUS_SUSPENDED as usize].ranges,
+            vec![(0, 1), (3, 5), (u32::MAX - 1, u32::MAX)]
+        );
+
+        // with another status treated as valid
+        let split = StatusSplit::with_valid(STATUS_SUSPENDED, StatusSize::Two, |_| {
+            RangeParser::new(RangeQueue::default())
+        });
+        let results =
+            process_status_bitmap(&[0b1000_0000], StatusSize::Two, BitOrder::Lsb, split).unwrap();
+        assert_eq!(
+            results[STATUS_VALID as usize].ranges,
+            vec![(0, 4), (u32::MAX - 1, u32::MAX)]
+        );
+        assert_eq!(
+            results[STATUS_SUSPENDED as usize].ranges,
+            vec![(0, 1), (3, u32::MAX)]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_zipped() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0x00, 0x20, 0x00]).unwrap();
+        let zipped = encoder.finish().unwrap();
+        let parser = RangeParser::new(RangeQueue::default());
+        let filter = StatusFilter::new(parser);
+        let collect = process_zipped_status_reader(
+            zipped.as_slice(),
+            StatusSize::Four,
+            BitOrder::Msb,
+            filter,
+        )
+        .unwrap();
+        assert_eq!(collect.ranges, vec![(0, 3), (3, u32::MAX)]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Import and export of W3C Bitstring Status Lists.
+//!
+//! An `encodedList` is the multibase (`u` prefixed) base64url encoding of a
+//! GZIP-compressed bitstring, where status list index 0 is the most
+//! significant bit of the first byte. This matches the bitmap format read by
+//! `process_zipped_bits`, except that status list indices start at zero while
+//! registry indices start at one.
+
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::input::{process_zipped_reader, BitSink};
+use crate::status::{process_zipped_status_reader, BitOrder, StatusSink, StatusSize};
+
+/// The minimum bitstring length required by the specification, in bytes.
+pub const MIN_LIST_BYTES: usize = 16 * 1024;
+
+/// Convert a `statusListIndex` to the corresponding registry index.
+pub fn registry_index(status_list_index: u32) -> Option<u32> {
+    status_list_index.checked_add(1)
+}
+
+/// Convert a registry index to the corresponding `statusListIndex`.
+pub fn status_list_index(registry_index: u32) -> Option<u32> {
+    registry_index.checked_sub(1)
+}
+
+/// Decode an `encodedList` value into the compressed bitstring. The value
+/// must carry the `u` multibase prefix.
+pub fn decode_list(encoded: &str) -> Result<Vec<u8>, Error> {
+    let encoded = encoded
+        .strip_prefix('u')
+        .ok_or_else(|| Error::InvalidInput("encodedList: missing multibase prefix".to_string()))?;
+    base64::decode_config(encoded.trim_end_matches('='), base64::URL_SAFE_NO_PAD)
+        .map_err(|err| Error::InvalidInput(format!("encodedList: {}", err)))
+}
+
+/// Feed the bits of an `encodedList` value to a `BitSink`.
+pub fn process_encoded_list<T>(encoded: &str, proc: T) -> Result<T::Result, Error>
+where
+    T: BitSink,
+{
+    let zipped = decode_list(encoded)?;
+    process_zipped_reader(zipped.as_slice(), proc)
+}
+
+/// Feed the entries of an `encodedList` value with a `statusSize` greater
+/// than one to a `StatusSink`.
+pub fn process_encoded_status_list<T>(
+    encoded: &str,
+    size: StatusSize,
+    proc: T,
+) -> Result<T::Result, Error>
+where
+    T: StatusSink,
+{
+    let zipped = decode_list(encoded)?;
+    process_zipped_status_reader(zipped.as_slice(), size, BitOrder::Msb, proc)
+}
+
+/// Extract the `encodedList` from a status list credential.
+pub fn credential_encoded_list(credential: &str) -> Result<String, Error> {
+    let value: Value = serde_json::from_str(credential)
+        .map_err(|err| Error::InvalidInput(format!("credential: {}", err)))?;
+    value
+        .get("credentialSubject")
+        .and_then(|subject| subject.get("encodedList"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| Error::InvalidInput("credential has no encodedList".to_string()))
+}
+
+/// Feed the bits of a status list credential to a `BitSink`.
+pub fn process_status_list_credential<T>(credential: &str, proc: T) -> Result<T::Result, Error>
+where
+    T: BitSink,
+{
+    let encoded = credential_encoded_list(credential)?;
+    process_encoded_list(&encoded, proc)
+}
+
+/// Produce an `encodedList` value for a bitmap, padding it with zero (valid)
+/// entries to the minimum length required by the specification.
+pub fn encode_list(bitmap: &[u8]) -> Result<String, Error> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(bitmap)?;
+    if bitmap.len() < MIN_LIST_BYTES {
+        encoder.write_all(&vec![0u8; MIN_LIST_BYTES - bitmap.len()])?;
+    }
+    let zipped = encoder.finish()?;
+    Ok(format!(
+        "u{}",
+        base64::encode_config(&zipped, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::range::{RangeParser, RangeQueue};
+
+    // the example list from the Bitstring Status List specification, of
+    // 131072 zero bits
+    const SPEC_LIST: &str = "uH4sIAAAAAAAAA-3BMQEAAADCoPVPbQwfoAAAAAAAAAAAAAAAAAAAAIC3AYbSVKsAQAAA";
+
+    #[test]
+    fn test_decode_spec_example() {
+        let parser = RangeParser::new(RangeQueue::default());
+        let collect = process_encoded_list(SPEC_LIST, parser).unwrap();
+        assert_eq!(collect.ranges, vec![(0, u32::MAX)]);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut bitmap = vec![0u8; 4];
+        // status list indices 0, 9 and 10
+        bitmap[0] = 0x80;
+        bitmap[1] = 0x60;
+        let encoded = encode_list(&bitmap).unwrap();
+        assert!(encoded.starts_with('u'));
+
+        let credential = format!(
+            r#"{{
+                "@context": ["https://www.w3.org/ns/credentials/v2"],
+                "type": ["VerifiableCredential", "BitstringStatusListCredential"],
+                "credentialSubject": {{
+                    "type": "BitstringStatusList",
+                    "statusPurpose": "revocation",
+                    "encodedList": "{}"
+                }}
+            }}"#,
+            encoded
+        );
+        let parser = RangeParser::new(RangeQueue::default());
+        let collect = process_status_list_credential(&credential, parser).unwrap();
+        let first = registry_index(0).unwrap();
+        let (second, third) = (registry_index(9).unwrap(), registry_index(10).unwrap());
+        assert_eq!(
+            collect.ranges,
+            vec![(0, first), (first, second), (third, u32::MAX)]
+        );
+        assert_eq!(status_list_index(third), Some(10));
+        assert_eq!(registry_index(u32::MAX), None);
+    }
+
+    #[test]
+    fn test_status_size() {
+        use crate::status::StatusFilter;
+
+        // 2-bit entries 0 to 3 are 0, 1, 2 and 3
+        let encoded = encode_list(&[0x1b]).unwrap();
+        let filter = StatusFilter::new(RangeParser::new(RangeQueue::default()));
+        let collect = process_encoded_status_list(&encoded, StatusSize::Two, filter).unwrap();
+        assert_eq!(
+            collect.ranges,
+            vec![
+                (0, registry_index(1).unwrap()),
+                (registry_index(3).unwrap(), u32::MAX)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let parser = RangeParser::new(RangeQueue::default());
+        assert!(matches!(
+            process_status_list_credential("{}", parser),
+            Err(Error::InvalidInput(_))
+        ));
+        let parser = RangeParser::new(RangeQueue::default());
+        assert!(matches!(
+            process_encoded_list("u!!", parser),
+            Err(Error::InvalidInput(_))
+        ));
+        // the spec example without its multibase prefix
+        assert!(matches!(
+            decode_list(&SPEC_LIST[1..]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
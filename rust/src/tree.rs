@@ -1,7 +1,15 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::error::Error;
+
 pub trait TreeFold {
     type Leaf;
     type Target: Clone;
-    type Error: std::fmt::Debug;
+    type Error: core::fmt::Debug;
 
     fn input(&mut self, leaf: &Self::Leaf) -> Result<Self::Target, Self::Error>;
 
@@ -11,19 +19,103 @@ pub trait TreeFold {
     fn end_fill(&mut self) {}
 }
 
-pub struct TreeFolder<T: TreeFold> {
+/// The state of a `TreeFolder`: the roots of the complete subtrees folded
+/// so far, from the largest, and the number of leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeFrontier<T> {
+    pub stack: Vec<T>,
+    pub leaf_count: usize,
+}
+
+impl TreeFrontier<Vec<u8>> {
+    /// Encode as the leaf count (u64) and hash size (u32), both big-endian,
+    /// followed by the subtree hashes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let hash_size = self.stack.first().map_or(0, Vec::len);
+        let mut out = Vec::with_capacity(12 + self.stack.len() * hash_size);
+        out.extend_from_slice(&(self.leaf_count as u64).to_be_bytes());
+        out.extend_from_slice(&(hash_size as u32).to_be_bytes());
+        for hash in &self.stack {
+            out.extend_from_slice(hash);
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 12 {
+            return Err(Error::InvalidInput("truncated frontier".to_string()));
+        }
+        let leaf_count = u64::from_be_bytes(data[..8].try_into().unwrap());
+        let leaf_count: usize = leaf_count
+            .try_into()
+            .map_err(|_| Error::InvalidInput(format!("invalid leaf count: {}", leaf_count)))?;
+        let hash_size = u32::from_be_bytes(data[8..12].try_into().unwrap()) as usize;
+        let count = leaf_count.count_ones() as usize;
+        let hashes = &data[12..];
+        if (hash_size == 0 && count > 0) || hashes.len() != count * hash_size {
+            return Err(Error::InvalidInput("invalid frontier length".to_string()));
+        }
+        let stack = if count == 0 {
+            vec![]
+        } else {
+            hashes.chunks_exact(hash_size).map(<[u8]>::to_vec).collect()
+        };
+        Ok(Self { stack, leaf_count })
+    }
+}
+
+/// Observes each node created by a `TreeFolder`, given its level above the
+/// leaves and its position from the left within that level. Nodes joining
+/// the peaks of an unfilled tree are placed where they would fall in the
+/// filled tree. The nodes of fill subtrees are each reported, with `fill`
+/// set.
+pub trait NodeVisitor<T> {
+    fn visit(&mut self, level: usize, position: usize, node: &T, fill: bool);
+}
+
+impl<T> NodeVisitor<T> for () {
+    fn visit(&mut self, _level: usize, _position: usize, _node: &T, _fill: bool) {}
+}
+
+impl<T, F> NodeVisitor<T> for F
+where
+    F: FnMut(usize, usize, &T, bool),
+{
+    fn visit(&mut self, level: usize, position: usize, node: &T, fill: bool) {
+        self(level, position, node, fill)
+    }
+}
+
+/// The root, the base and the visitor returned by `TreeFolder::finish`.
+pub type FinishResult<T, V> = (Option<<T as TreeFold>::Target>, T, V);
+
+pub struct TreeFolder<T: TreeFold, V = ()> {
     base: T,
     stack: Vec<T::Target>,
     leaf_count: usize,
+    visitor: V,
 }
 
 impl<T: TreeFold> TreeFolder<T> {
     pub fn new(base: T) -> Self {
-        Self {
-            base,
-            stack: vec![],
-            leaf_count: 0,
+        Self::with_visitor(base, ())
+    }
+
+    /// Continue folding from a saved frontier.
+    pub fn resume(base: T, frontier: TreeFrontier<T::Target>) -> Result<Self, Error> {
+        if frontier.stack.len() != frontier.leaf_count.count_ones() as usize {
+            return Err(Error::InvalidInput(format!(
+                "expected {} subtrees for {} leaves",
+                frontier.leaf_count.count_ones(),
+                frontier.leaf_count
+            )));
         }
+        Ok(Self {
+            base,
+            stack: frontier.stack,
+            leaf_count: frontier.leaf_count,
+            visitor: (),
+        })
     }
 
     #[allow(unused)]
@@ -42,14 +134,37 @@ impl<T: TreeFold> TreeFolder<T> {
         }
         inst.result()
     }
+}
+
+impl<T: TreeFold, V: NodeVisitor<T::Target>> TreeFolder<T, V> {
+    pub fn with_visitor(base: T, visitor: V) -> Self {
+        Self {
+            base,
+            stack: vec![],
+            leaf_count: 0,
+            visitor,
+        }
+    }
+
+    pub fn frontier(&self) -> TreeFrontier<T::Target> {
+        TreeFrontier {
+            stack: self.stack.clone(),
+            leaf_count: self.leaf_count,
+        }
+    }
 
     pub fn push(&mut self, leaf: &T::Leaf) -> Result<(), T::Error> {
         let mut h = self.base.input(leaf)?;
+        self.visitor.visit(0, self.leaf_count, &h, false);
         let mut b = self.leaf_count + 1;
+        let mut level = 0;
         while b & 1 == 0 {
             let left = self.stack.pop().unwrap();
             h = self.base.fold(&left, &h)?;
             b = b >> 1;
+            level += 1;
+            self.visitor
+                .visit(level, self.leaf_count >> level, &h, false);
         }
         self.stack.push(h);
         self.leaf_count += 1;
@@ -66,54 +181,52 @@ impl<T: TreeFold> TreeFolder<T> {
         Ok(())
     }
 
+    fn fill_node(
+        base: &mut T,
+        fill_cache: &mut Vec<T::Target>,
+        fill_input: &T::Leaf,
+        depth: usize,
+    ) -> Result<(), T::Error> {
+        while fill_cache.len() <= depth {
+            let h = match fill_cache.last() {
+                None => base.input(fill_input)?,
+                Some(prev) => base.fold(prev, prev)?,
+            };
+            fill_cache.push(h);
+        }
+        Ok(())
+    }
+
     pub fn fill(&mut self, fill_input: T::Leaf) -> Result<usize, T::Error> {
         let mut fill_cache: Vec<T::Target> = vec![];
 
-        let mut filler = |depth: usize, base: &mut T| -> Result<T::Target, T::Error> {
-            let mut d = fill_cache.len();
-            if d > depth {
-                return Ok(fill_cache[depth].clone());
-            }
-            let mut h = if d == 0 {
-                base.input(&fill_input)?
-            } else {
-                let prev = fill_cache[d - 1].clone();
-                base.fold(&prev, &prev)?
-            };
-            loop {
-                fill_cache.push(h.clone());
-                d += 1;
-                if d > depth {
-                    break;
-                }
-                h = base.fold(&h, &h)?;
-            }
-            Ok(h)
-        };
-
         let leaf_count = self.leaf_count;
         let fill_size = leaf_count.next_power_of_two();
         let mut fill_count = fill_size - leaf_count;
-        let mut fill_depth: isize = 0;
+        let mut fill_depth = 0;
         let mut leaf_count_filled = leaf_count;
 
         while fill_count > 0 {
             if fill_count & 1 != 0 {
                 self.base.start_fill();
-                let mut h = filler(fill_depth as usize, &mut self.base)?;
+                Self::fill_node(&mut self.base, &mut fill_cache, &fill_input, fill_depth)?;
                 self.base.end_fill();
-                leaf_count_filled += 1 << fill_depth;
-                let mut b = leaf_count_filled;
-                let mut c: isize = 0;
-                while b & 1 == 0 {
-                    b = b >> 1;
-                    c += 1;
+                let position = leaf_count_filled >> fill_depth;
+                for (level, node) in fill_cache[..=fill_depth].iter().enumerate() {
+                    let width = 1 << (fill_depth - level);
+                    for pos in position * width..(position + 1) * width {
+                        self.visitor.visit(level, pos, node, true);
+                    }
                 }
-                c -= fill_depth;
-                while c > 0 {
+                leaf_count_filled += 1 << fill_depth;
+                let mut h = fill_cache[fill_depth].clone();
+                let mut level = fill_depth;
+                while level < leaf_count_filled.trailing_zeros() as usize {
                     let left = self.stack.pop().unwrap();
                     h = self.base.fold(&left, &h)?;
-                    c -= 1;
+                    level += 1;
+                    self.visitor
+                        .visit(level, (leaf_count_filled - 1) >> level, &h, false);
                 }
                 self.stack.push(h);
             }
@@ -124,26 +237,61 @@ impl<T: TreeFold> TreeFolder<T> {
         Ok(fill_count)
     }
 
-    pub fn result(mut self) -> Result<(Option<T::Target>, T), T::Error> {
+    pub fn result(self) -> Result<(Option<T::Target>, T), T::Error> {
+        let (result, base, _) = self.finish()?;
+        Ok((result, base))
+    }
+
+    /// Take the root as with `result`, also returning the visitor.
+    pub fn finish(mut self) -> Result<FinishResult<T, V>, T::Error> {
         let result = if self.stack.is_empty() {
             None
         } else if self.stack.len() > 1 {
+            // the heights of the peaks before the last, from the smallest
+            let leaf_count = self.leaf_count;
+            let mut heights = (0..usize::BITS as usize)
+                .filter(|height| leaf_count >> height & 1 != 0)
+                .skip(1);
             let mut root = self.stack.pop().unwrap();
             while !self.stack.is_empty() {
                 root = self.base.fold(&self.stack.pop().unwrap(), &root)?;
+                let level = heights.next().unwrap() + 1;
+                self.visitor.visit(level, leaf_count >> level, &root, false);
             }
             self.stack.push(root.clone());
             Some(root)
         } else {
             Some(self.stack[0].clone())
         };
-        Ok((result, self.base))
+        Ok((result, self.base, self.visitor))
     }
 
     pub fn len(&self) -> usize {
         self.leaf_count
     }
 
+    /// The roots of the complete subtrees folded so far, from the largest.
+    /// Without filling, these are the peaks of a Merkle mountain range.
+    pub fn peaks(&self) -> &[T::Target] {
+        &self.stack
+    }
+
+    pub fn base(&self) -> &T {
+        &self.base
+    }
+
+    pub fn visitor(&self) -> &V {
+        &self.visitor
+    }
+
+    pub fn visitor_mut(&mut self) -> &mut V {
+        &mut self.visitor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+
     pub fn update_base<F>(&mut self, f: F)
     where
         F: FnOnce(&mut T),
@@ -176,6 +324,45 @@ pub(crate) mod test {
         }
     }
 
+    #[test]
+    fn test_resume() {
+        let leaves: Vec<String> = (0..11).map(|n| n.to_string()).collect();
+        let (expect, _) = TreeFolder::fold(TestFold {}, leaves.clone(), None).unwrap();
+        for split in 0..leaves.len() {
+            let mut folder = TreeFolder::new(TestFold {});
+            folder.extend(leaves[..split].to_vec()).unwrap();
+            let frontier = folder.frontier();
+            assert_eq!(frontier.stack.len(), split.count_ones() as usize);
+            let mut folder = TreeFolder::resume(TestFold {}, frontier).unwrap();
+            folder.extend(leaves[split..].to_vec()).unwrap();
+            assert_eq!(folder.result().unwrap().0, expect);
+        }
+
+        let frontier = TreeFrontier {
+            stack: vec!["[0,1]".to_string()],
+            leaf_count: 3,
+        };
+        assert!(TreeFolder::resume(TestFold {}, frontier).is_err());
+    }
+
+    #[test]
+    fn test_frontier_bytes() {
+        let frontier = TreeFrontier {
+            stack: vec![vec![1u8; 4], vec![2u8; 4]],
+            leaf_count: 5,
+        };
+        let bytes = frontier.to_bytes();
+        assert_eq!(bytes.len(), 12 + 8);
+        assert_eq!(TreeFrontier::from_bytes(&bytes).unwrap(), frontier);
+        assert!(TreeFrontier::from_bytes(&bytes[..19]).is_err());
+
+        let empty = TreeFrontier {
+            stack: vec![],
+            leaf_count: 0,
+        };
+        assert_eq!(TreeFrontier::from_bytes(&empty.to_bytes()).unwrap(), empty);
+    }
+
     #[test]
     fn test_unfilled() {
         let leaves = (0..=4).map(|n| n.to_string());